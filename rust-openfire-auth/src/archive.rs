@@ -0,0 +1,184 @@
+//! Persistent message archiving (XEP-0313), backed by SQLite
+//!
+//! This covers local storage and paged retrieval only. Querying a remote
+//! MAM archive over the wire is `communication::OpenFireClient::fetch_remote_history`'s
+//! job (see `mam` for the IQ payload/result-stanza wire format) -- results it
+//! pages in get stored here the same way a live inbound message would be.
+
+use crate::communication::{Message, MessageType};
+use crate::error::{OpenFireError, Result};
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+
+/// A SQLite-backed store of every inbound/outbound message, keyed by the
+/// conversation's room or peer JID
+pub struct MessageArchive {
+    path: PathBuf,
+}
+
+impl MessageArchive {
+    /// Open (creating if necessary) the archive database at `path`
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let archive = Self {
+            path: path.as_ref().to_path_buf(),
+        };
+
+        archive.with_connection(|conn| {
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS messages (
+                    jid TEXT NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    id TEXT NOT NULL,
+                    from_jid TEXT NOT NULL,
+                    body TEXT NOT NULL,
+                    message_type TEXT NOT NULL,
+                    thread TEXT,
+                    PRIMARY KEY (jid, timestamp, id)
+                )",
+                [],
+            )?;
+            Ok(())
+        })?;
+
+        Ok(archive)
+    }
+
+    /// Persist `message` under the conversation key `jid` (the room or peer it belongs to)
+    pub fn store(&self, jid: &str, message: &Message) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO messages (jid, timestamp, id, from_jid, body, message_type, thread)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    jid,
+                    message.timestamp as i64,
+                    message.id,
+                    message.from,
+                    message.body,
+                    message_type_to_str(&message.message_type),
+                    message.thread,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Fetch up to `limit` archived messages for `jid`, returned oldest first.
+    /// When `before` is set, only messages strictly earlier than that unix
+    /// timestamp are returned, for paging backward through history.
+    pub fn get_history(&self, jid: &str, limit: usize, before: Option<u64>) -> Result<Vec<Message>> {
+        self.with_connection(|conn| {
+            let mut statement = conn.prepare(
+                "SELECT timestamp, id, from_jid, body, message_type, thread FROM messages
+                 WHERE jid = ?1 AND (?2 IS NULL OR timestamp < ?2)
+                 ORDER BY timestamp DESC LIMIT ?3",
+            )?;
+
+            let rows = statement.query_map(
+                params![jid, before.map(|b| b as i64), limit as i64],
+                |row| {
+                    let message_type: String = row.get(4)?;
+                    Ok(Message {
+                        id: row.get(1)?,
+                        from: row.get(2)?,
+                        to: jid.to_string(),
+                        message_type: message_type_from_str(&message_type),
+                        subject: None,
+                        body: row.get(3)?,
+                        timestamp: row.get::<_, i64>(0)? as u64,
+                        thread: row.get(5)?,
+                    })
+                },
+            )?;
+
+            let mut messages = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+            messages.reverse();
+            Ok(messages)
+        })
+    }
+
+    fn with_connection<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T> {
+        let conn = Connection::open(&self.path).map_err(|e| OpenFireError::ConfigError {
+            message: format!("Failed to open message archive at {}: {}", self.path.display(), e),
+        })?;
+
+        f(&conn).map_err(|e| OpenFireError::SerializationError {
+            message: format!("Message archive query failed: {}", e),
+        })
+    }
+}
+
+fn message_type_to_str(message_type: &MessageType) -> &'static str {
+    match message_type {
+        MessageType::Chat => "chat",
+        MessageType::GroupChat => "groupchat",
+        MessageType::Headline => "headline",
+        MessageType::Normal => "normal",
+        MessageType::Error => "error",
+    }
+}
+
+fn message_type_from_str(value: &str) -> MessageType {
+    match value {
+        "groupchat" => MessageType::GroupChat,
+        "headline" => MessageType::Headline,
+        "error" => MessageType::Error,
+        "normal" => MessageType::Normal,
+        _ => MessageType::Chat,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_archive_path(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("openfire_archive_{}_{}.sqlite", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_store_then_get_history_returns_oldest_first() {
+        let path = temp_archive_path("basic");
+        let archive = MessageArchive::new(&path).unwrap();
+
+        let mut first = Message::new_chat("a@b".to_string(), "room@conf".to_string(), "first".to_string());
+        first.timestamp = 100;
+        let mut second = Message::new_chat("a@b".to_string(), "room@conf".to_string(), "second".to_string());
+        second.timestamp = 200;
+
+        archive.store("room@conf", &first).unwrap();
+        archive.store("room@conf", &second).unwrap();
+
+        let history = archive.get_history("room@conf", 10, None).unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].body, "first");
+        assert_eq!(history[1].body, "second");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_get_history_respects_before_cursor_for_paging() {
+        let path = temp_archive_path("paging");
+        let archive = MessageArchive::new(&path).unwrap();
+
+        let mut first = Message::new_chat("a@b".to_string(), "room@conf".to_string(), "first".to_string());
+        first.timestamp = 100;
+        let mut second = Message::new_chat("a@b".to_string(), "room@conf".to_string(), "second".to_string());
+        second.timestamp = 200;
+
+        archive.store("room@conf", &first).unwrap();
+        archive.store("room@conf", &second).unwrap();
+
+        let history = archive.get_history("room@conf", 10, Some(200)).unwrap();
+
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].body, "first");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
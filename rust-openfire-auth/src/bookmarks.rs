@@ -0,0 +1,167 @@
+//! PubSub Bookmarks (XEP-0402, `urn:xmpp:bookmarks:1`) IQ payloads and
+//! response parsing. Driving the IQ round-trip is `communication::OpenFireClient`'s job.
+
+use crate::error::{OpenFireError, Result};
+use crate::stanza::{escape_xml, find_attr};
+use quick_xml::events::Event;
+use quick_xml::reader::NsReader;
+
+pub(crate) const BOOKMARKS_NODE: &str = "urn:xmpp:bookmarks:1";
+
+/// A persisted MUC room bookmark (XEP-0402 `<conference/>`), round-tripped
+/// through the user's server-side PubSub `urn:xmpp:bookmarks:1` node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conference {
+    pub jid: String,
+    pub name: Option<String>,
+    pub autojoin: bool,
+    pub nickname: Option<String>,
+    /// The room's last-known subject. Not part of XEP-0402 proper; persisted
+    /// as an extra child element so reconnecting can restore the topic
+    /// without a live MUC query.
+    pub subject: Option<String>,
+}
+
+/// Build the `<iq type='set'>` PubSub payload that publishes `bookmark` as an
+/// item in the `urn:xmpp:bookmarks:1` node, keyed by its room jid
+pub(crate) fn publish_payload(bookmark: &Conference) -> String {
+    let mut conference = format!(
+        "<conference xmlns='{}' name='{}' autojoin='{}'>",
+        BOOKMARKS_NODE,
+        escape_xml(bookmark.name.as_deref().unwrap_or(&bookmark.jid)),
+        bookmark.autojoin
+    );
+    if let Some(nick) = &bookmark.nickname {
+        conference.push_str(&format!("<nick>{}</nick>", escape_xml(nick)));
+    }
+    if let Some(subject) = &bookmark.subject {
+        conference.push_str(&format!("<subject>{}</subject>", escape_xml(subject)));
+    }
+    conference.push_str("</conference>");
+
+    format!(
+        "<pubsub xmlns='http://jabber.org/protocol/pubsub'><publish node='{}'>\
+         <item id='{}'>{}</item></publish></pubsub>",
+        BOOKMARKS_NODE,
+        escape_xml(&bookmark.jid),
+        conference
+    )
+}
+
+/// Build the `<iq type='get'>` PubSub payload requesting every item
+/// currently stored in the `urn:xmpp:bookmarks:1` node
+pub(crate) fn items_request_payload() -> String {
+    format!(
+        "<pubsub xmlns='http://jabber.org/protocol/pubsub'><items node='{}'/></pubsub>",
+        BOOKMARKS_NODE
+    )
+}
+
+/// Parse a `urn:xmpp:bookmarks:1` PubSub items result into its `Conference` bookmarks
+pub(crate) fn parse_bookmarks(xml: &str) -> Result<Vec<Conference>> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut bookmarks = Vec::new();
+    let mut current_jid: Option<String> = None;
+    let mut current_name: Option<String> = None;
+    let mut current_autojoin = false;
+    let mut current_nickname: Option<String> = None;
+    let mut current_subject: Option<String> = None;
+    let mut current_child: Option<String> = None;
+
+    loop {
+        match reader.read_event().map_err(|e| OpenFireError::XmppProtocolError {
+            message: format!("Failed to parse bookmarks response: {}", e),
+        })? {
+            Event::Start(e) | Event::Empty(e) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match local.as_str() {
+                    "item" => current_jid = find_attr(&e, "id"),
+                    "conference" => {
+                        current_name = find_attr(&e, "name");
+                        current_autojoin = find_attr(&e, "autojoin").as_deref() == Some("true");
+                    }
+                    _ => {}
+                }
+                current_child = Some(local);
+            }
+            Event::Text(t) => {
+                let text = t.unescape().unwrap_or_default().into_owned();
+                match current_child.as_deref() {
+                    Some("nick") => current_nickname = Some(text),
+                    Some("subject") => current_subject = Some(text),
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if local == "item" {
+                    if let Some(jid) = current_jid.take() {
+                        bookmarks.push(Conference {
+                            jid,
+                            name: current_name.take(),
+                            autojoin: current_autojoin,
+                            nickname: current_nickname.take(),
+                            subject: current_subject.take(),
+                        });
+                    }
+                    current_autojoin = false;
+                }
+                current_child = None;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(bookmarks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_payload_includes_conference_nick_and_subject() {
+        let bookmark = Conference {
+            jid: "room@conference.localhost".to_string(),
+            name: Some("Lounge".to_string()),
+            autojoin: true,
+            nickname: Some("bob".to_string()),
+            subject: Some("Today's topic".to_string()),
+        };
+
+        let payload = publish_payload(&bookmark);
+        assert!(payload.contains("<item id='room@conference.localhost'>"));
+        assert!(payload.contains("name='Lounge'"));
+        assert!(payload.contains("autojoin='true'"));
+        assert!(payload.contains("<nick>bob</nick>"));
+        assert!(payload.contains("<subject>Today&apos;s topic</subject>"));
+    }
+
+    #[test]
+    fn test_parse_bookmarks_extracts_each_conference_item() {
+        let xml = "<pubsub xmlns='http://jabber.org/protocol/pubsub'>\
+            <items node='urn:xmpp:bookmarks:1'>\
+            <item id='room1@conference.localhost'>\
+            <conference xmlns='urn:xmpp:bookmarks:1' name='Room One' autojoin='true'>\
+            <nick>alice</nick><subject>Welcome</subject></conference></item>\
+            <item id='room2@conference.localhost'>\
+            <conference xmlns='urn:xmpp:bookmarks:1' name='Room Two' autojoin='false'/></item>\
+            </items></pubsub>";
+
+        let bookmarks = parse_bookmarks(xml).unwrap();
+        assert_eq!(bookmarks.len(), 2);
+
+        assert_eq!(bookmarks[0].jid, "room1@conference.localhost");
+        assert_eq!(bookmarks[0].name, Some("Room One".to_string()));
+        assert!(bookmarks[0].autojoin);
+        assert_eq!(bookmarks[0].nickname, Some("alice".to_string()));
+        assert_eq!(bookmarks[0].subject, Some("Welcome".to_string()));
+
+        assert_eq!(bookmarks[1].jid, "room2@conference.localhost");
+        assert!(!bookmarks[1].autojoin);
+        assert_eq!(bookmarks[1].nickname, None);
+    }
+}
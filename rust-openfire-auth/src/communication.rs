@@ -1,13 +1,24 @@
 //! OpenFire communication module for XMPP messaging
 
-use crate::auth::{AuthManager, Credentials};
+use crate::archive::MessageArchive;
+use crate::auth::{AuthManager, AuthResult, Credentials};
+use crate::bookmarks;
 use crate::config::Config;
+use crate::discovery::{self, DiscoItem, RoomInfo, ServerInfo};
 use crate::error::{OpenFireError, Result};
+use crate::mam;
+use crate::sso::SsoProviderConfig;
+use crate::stanza::{self, escape_xml};
+use crate::upload::{self, UploadSlot};
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// XMPP message types
@@ -46,7 +57,7 @@ impl Message {
             thread: None,
         }
     }
-    
+
     pub fn new_group_chat(from: String, to: String, body: String) -> Self {
         Self {
             id: generate_message_id(),
@@ -59,6 +70,38 @@ impl Message {
             thread: None,
         }
     }
+
+    /// Serialize this message to an XMPP `<message/>` stanza
+    pub fn to_xml(&self) -> String {
+        let type_attr = match self.message_type {
+            MessageType::Chat => "chat",
+            MessageType::GroupChat => "groupchat",
+            MessageType::Headline => "headline",
+            MessageType::Normal => "normal",
+            MessageType::Error => "error",
+        };
+
+        let mut xml = format!(
+            "<message type='{}' id='{}' from='{}' to='{}'>",
+            type_attr,
+            escape_xml(&self.id),
+            escape_xml(&self.from),
+            escape_xml(&self.to)
+        );
+
+        if let Some(subject) = &self.subject {
+            xml.push_str(&format!("<subject>{}</subject>", escape_xml(subject)));
+        }
+
+        xml.push_str(&format!("<body>{}</body>", escape_xml(&self.body)));
+
+        if let Some(thread) = &self.thread {
+            xml.push_str(&format!("<thread>{}</thread>", escape_xml(thread)));
+        }
+
+        xml.push_str("</message>");
+        xml
+    }
 }
 
 /// Presence status
@@ -102,6 +145,33 @@ impl Presence {
         self.priority = priority;
         self
     }
+
+    /// Serialize this presence to an XMPP `<presence/>` stanza
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<presence");
+        if self.status == PresenceStatus::Unavailable {
+            xml.push_str(" type='unavailable'");
+        }
+        xml.push('>');
+
+        let show = match self.status {
+            PresenceStatus::Away => Some("away"),
+            PresenceStatus::DoNotDisturb => Some("dnd"),
+            PresenceStatus::ExtendedAway => Some("xa"),
+            PresenceStatus::Available | PresenceStatus::Unavailable | PresenceStatus::Invisible => None,
+        };
+        if let Some(show) = show {
+            xml.push_str(&format!("<show>{}</show>", show));
+        }
+
+        if let Some(status_message) = &self.status_message {
+            xml.push_str(&format!("<status>{}</status>", escape_xml(status_message)));
+        }
+
+        xml.push_str(&format!("<priority>{}</priority>", self.priority));
+        xml.push_str("</presence>");
+        xml
+    }
 }
 
 /// Contact information
@@ -132,77 +202,513 @@ pub enum XmppEvent {
     PresenceUpdated(Presence),
     ContactUpdated(Contact),
     ConnectionStateChanged(String),
+    /// A raw `<iq>` response, correlated by stanza id. IQs with a pending
+    /// waiter (see `OpenFireClient::send_iq`) are consumed internally and
+    /// never reach the event callback; this only fires for unsolicited ones.
+    IqReceived { id: String, xml: String },
+    /// A peer's answer to one of our own subscription actions, or their
+    /// unilateral teardown of ours (RFC 6121 `subscribed`/`unsubscribe`/
+    /// `unsubscribed` presence). `pump_inbound_stanzas` applies it to the
+    /// matching `Contact.subscription` and re-surfaces it as
+    /// `ContactUpdated`; it never reaches the event callback as-is.
+    SubscriptionPresence { jid: String, kind: String },
+    /// The peer in `jid` asked to subscribe to our presence (RFC 6121
+    /// `<presence type='subscribe'/>`); respond with
+    /// `OpenFireClient::approve_subscription` or `unsubscribe`
+    SubscriptionRequest(String),
+    /// A single result from a XEP-0313 MAM archive query, correlated to the
+    /// query that requested it via `query_id`. `pump_inbound_stanzas`
+    /// archives it locally the same way a live inbound message is, then
+    /// forwards it so a caller paging through remote history can render
+    /// results as they stream in, before the query's `<fin/>` arrives.
+    MamResult { query_id: String, message: Message },
     Error(String),
 }
 
 /// Message callback type
 pub type EventCallback = Box<dyn Fn(XmppEvent) + Send + Sync>;
 
+/// How many archived messages `join_room` replays by default
+const DEFAULT_HISTORY_REPLAY_LIMIT: usize = 50;
+
+/// Strip the resource part off a JID, giving the bare room or peer JID used
+/// as the message archive's conversation key
+fn bare_jid(jid: &str) -> String {
+    jid.split('/').next().unwrap_or(jid).to_string()
+}
+
+/// Apply an *inbound* RFC 6121 subscription-presence `kind` (`subscribed`,
+/// `unsubscribe`, or `unsubscribed`) to a contact's current `subscription`
+/// (`none`/`from`/`to`/`both`), returning the resulting state. `subscribed`
+/// and `unsubscribed` are the peer's answer to (or teardown of) a
+/// subscription *we* hold on *them* (the `to` bit); `unsubscribe` is the
+/// peer cancelling *their* subscription to *us* (the `from` bit).
+fn apply_subscription_transition(current: &str, kind: &str) -> String {
+    let mut has_to = current == "to" || current == "both";
+    let mut has_from = current == "from" || current == "both";
+
+    match kind {
+        "subscribed" => has_to = true,
+        "unsubscribed" => has_to = false,
+        "unsubscribe" => has_from = false,
+        _ => {}
+    }
+
+    match (has_to, has_from) {
+        (true, true) => "both",
+        (true, false) => "to",
+        (false, true) => "from",
+        (false, false) => "none",
+    }
+    .to_string()
+}
+
 /// OpenFire XMPP client
 pub struct OpenFireClient {
     config: Config,
     auth_manager: Arc<Mutex<AuthManager>>,
     is_connected: bool,
     current_presence: Option<Presence>,
-    contacts: HashMap<String, Contact>,
-    chat_rooms: HashMap<String, ChatRoom>,
+    contacts: Arc<Mutex<HashMap<String, Contact>>>,
+    chat_rooms: Arc<Mutex<HashMap<String, ChatRoom>>>,
     event_tx: Option<mpsc::UnboundedSender<XmppEvent>>,
+    write_half: Option<Arc<Mutex<OwnedWriteHalf>>>,
+    archive: Option<Arc<MessageArchive>>,
+    /// Waiters for in-flight IQ requests, keyed by stanza id
+    pending_iqs: Arc<Mutex<HashMap<String, oneshot::Sender<String>>>>,
+    /// disco#info results already fetched this session, keyed by JID, so
+    /// other subsystems (MUC, file upload, MAM) can gate behavior on what the
+    /// server advertises without a fresh round trip each time
+    feature_cache: Arc<Mutex<HashMap<String, ServerInfo>>>,
+    /// In-flight MAM (XEP-0313) queries, mapping each query id to the
+    /// conversation jid (room or peer) its results should be archived under
+    /// -- `message.from` on a result can be either side of the
+    /// conversation, so `pump_inbound_stanzas` can't derive that key the way
+    /// it does for a live inbound message
+    mam_queries: Arc<Mutex<HashMap<String, String>>>,
 }
 
 impl OpenFireClient {
     /// Create a new OpenFire client
     pub fn new(config: Config) -> Result<Self> {
         let auth_manager = Arc::new(Mutex::new(AuthManager::new(config.clone())?));
-        
+
+        let archive = match &config.mam_archive_path {
+            Some(path) => Some(Arc::new(MessageArchive::new(path)?)),
+            None => None,
+        };
+
         Ok(Self {
             config,
             auth_manager,
             is_connected: false,
             current_presence: None,
-            contacts: HashMap::new(),
-            chat_rooms: HashMap::new(),
+            contacts: Arc::new(Mutex::new(HashMap::new())),
+            chat_rooms: Arc::new(Mutex::new(HashMap::new())),
             event_tx: None,
+            write_half: None,
+            archive,
+            pending_iqs: Arc::new(Mutex::new(HashMap::new())),
+            feature_cache: Arc::new(Mutex::new(HashMap::new())),
+            mam_queries: Arc::new(Mutex::new(HashMap::new())),
         })
     }
-    
-    /// Connect and authenticate to the OpenFire server
-    pub async fn connect(&mut self, credentials: Credentials) -> Result<()> {
+
+    /// Open a fresh TCP connection to `config.server:config.port`
+    async fn open_stream(config: &Config) -> Result<TcpStream> {
+        TcpStream::connect((config.server.as_str(), config.port))
+            .await
+            .map_err(|e| OpenFireError::ConnectionError {
+                message: format!(
+                    "Failed to open XMPP stream to {}:{}: {}",
+                    config.server, config.port, e
+                ),
+            })
+    }
+
+    /// Connect and authenticate to the OpenFire server. Opens the TCP stream
+    /// and performs a real SASL handshake over it via
+    /// `AuthManager::authenticate_live`; the stream is only ever handed to
+    /// `write_stanza`/`pump_inbound_stanzas` once that handshake has actually
+    /// completed. If the server doesn't finish stream negotiation within
+    /// `Config::auth_timeout`, the connection attempt fails outright --
+    /// there's no simulated-authentication fallback that would leave the
+    /// client believing it's connected over a socket the server never confirmed.
+    pub async fn connect(&mut self, credentials: Credentials) -> Result<AuthResult> {
         info!("Connecting to OpenFire server: {}", self.config.server);
-        
+
+        let auth_result = self.bind_live_stream(&credentials).await?;
+
+        info!("Successfully connected to OpenFire server");
+        self.emit_event(XmppEvent::ConnectionStateChanged("connected".to_string())).await;
+
+        Ok(auth_result)
+    }
+
+    /// Connect and authenticate via OAuth2/OIDC single sign-on: opens the TCP
+    /// stream, then drives `AuthManager::authenticate_sso_live` over it, which
+    /// runs the PKCE authorization-code flow (build the authorization URL,
+    /// capture the identity provider's redirect on a loopback listener,
+    /// exchange the code for an access token) and binds the resulting token
+    /// to the stream via a real SASL OAUTHBEARER exchange. The stream is only
+    /// ever handed to `write_stanza`/`pump_inbound_stanzas` once that exchange
+    /// has actually succeeded. The redirect wait can take much longer than a
+    /// password exchange, so (mirroring `authenticate_sso`'s own internal
+    /// timeout) this waits at least 60s rather than the raw `auth_timeout`;
+    /// like `bind_live_stream`, the `auth_manager` lock is held for the whole
+    /// exchange, so a concurrent `connect`/`reconnect` on the same client
+    /// blocks until the user finishes signing in or this times out.
+    pub async fn connect_sso(&mut self, provider: SsoProviderConfig) -> Result<AuthResult> {
+        info!("Connecting to OpenFire server via SSO: {}", self.config.server);
+
+        let mut stream = Self::open_stream(&self.config).await?;
+        let sso_timeout = self.config.auth_timeout.max(60);
+
         let mut auth_manager = self.auth_manager.lock().await;
-        let auth_result = auth_manager.authenticate(credentials).await?;
-        
+        let auth_result = match tokio::time::timeout(
+            Duration::from_secs(sso_timeout),
+            auth_manager.authenticate_sso_live(provider, &mut stream),
+        )
+        .await
+        {
+            Ok(result) => result?,
+            Err(_) => {
+                return Err(OpenFireError::TimeoutError { seconds: sso_timeout });
+            }
+        };
+        drop(auth_manager);
+
+        let auth_result = self.finish_binding(stream, auth_result, Vec::new()).await?;
+
+        info!("Successfully connected to OpenFire server via SSO");
+        self.emit_event(XmppEvent::ConnectionStateChanged("connected".to_string())).await;
+
+        Ok(auth_result)
+    }
+
+    /// Re-establish the connection after `pump_inbound_stanzas` reports a
+    /// dropped stream (`XmppEvent::ConnectionStateChanged("disconnected")` or
+    /// `XmppEvent::Error`). Unlike `connect`, this opens its stream and hands
+    /// it to `AuthManager::resume_live` first, which sends a real XEP-0198
+    /// `<resume previd='..' h='..'/>` and restores the prior session without
+    /// a fresh SASL exchange. Only when `resume_live` reports the resumption
+    /// isn't possible (no prior session, or the server replying `<failed/>`)
+    /// does this fall back to `bind_live_stream`, opening a fresh stream and
+    /// performing the same real live SASL handshake `connect` would --
+    /// `resume_live`'s stream already had a `<stream:stream>` opened and
+    /// negotiated over it, and a second SASL handshake can't safely restart
+    /// that same XML stream with another opening tag, so the fallback can't
+    /// reuse it. Existing roster/chat-room state is left untouched. Emits a
+    /// `ConnectionStateChanged` event on every outcome so a listener that saw
+    /// `"reconnecting"` always sees a matching `"connected"` or `"disconnected"`.
+    pub async fn reconnect(&mut self, credentials: Credentials) -> Result<AuthResult> {
+        info!("Reconnecting to OpenFire server: {}", self.config.server);
+        self.is_connected = false;
+        self.emit_event(XmppEvent::ConnectionStateChanged("reconnecting".to_string())).await;
+
+        match self.reconnect_inner(credentials).await {
+            Ok(live_result) => Ok(live_result),
+            Err(e) => {
+                self.emit_event(XmppEvent::ConnectionStateChanged("disconnected".to_string())).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn reconnect_inner(&mut self, credentials: Credentials) -> Result<AuthResult> {
+        // Skip opening a stream for resume_live entirely when there's no
+        // session to resume -- it would just open it, negotiate nothing, and
+        // return Ok(None), leaving the fallback below to pay for a second
+        // handshake anyway.
+        let has_session = self.auth_manager.lock().await.has_resumable_session();
+
+        let resumed = if has_session {
+            let mut stream = Self::open_stream(&self.config).await?;
+            let mut buffer = Vec::new();
+
+            let mut auth_manager = self.auth_manager.lock().await;
+            let resumed = match tokio::time::timeout(
+                Duration::from_secs(self.config.auth_timeout),
+                auth_manager.resume_live(&credentials, &mut stream, &mut buffer),
+            )
+            .await
+            {
+                Ok(result) => result?,
+                Err(_) => {
+                    return Err(OpenFireError::TimeoutError {
+                        seconds: self.config.auth_timeout,
+                    });
+                }
+            };
+            drop(auth_manager);
+
+            match resumed {
+                // `buffer` may already hold stanzas the server replayed right
+                // after `<resumed/>` -- hand it to finish_binding so the
+                // inbound pump picks them up instead of silently dropping them.
+                Some(auth_result) => Some(self.finish_binding(stream, auth_result, buffer).await?),
+                // Don't reuse `stream`: it already had one `<stream:stream>`
+                // open tag written and negotiated for the resume attempt, and
+                // `bind_live_stream`'s SASL exchange always writes its own --
+                // sending a second one on the same socket isn't valid
+                // XML-stream framing. Drop it and pay for a fresh TCP
+                // connection instead.
+                None => None,
+            }
+        } else {
+            None
+        };
+
+        // `resumed` is already bound (finish_binding ran above) whenever
+        // resume_live succeeded; otherwise fall back to a fresh connection.
+        // Don't reuse resume_live's stream for that fallback: it already had
+        // one `<stream:stream>` open tag written and negotiated for the
+        // resume attempt, and `bind_live_stream`'s SASL exchange always
+        // writes its own -- sending a second one on the same socket isn't
+        // valid XML-stream framing. Pay for a fresh TCP connection instead.
+        let live_result = match resumed {
+            Some(auth_result) => auth_result,
+            None => self.bind_live_stream(&credentials).await?,
+        };
+
+        info!(
+            "Reconnected to OpenFire server as {}",
+            live_result.full_jid.as_deref().unwrap_or("unknown"),
+        );
+        self.emit_event(XmppEvent::ConnectionStateChanged("connected".to_string())).await;
+
+        Ok(live_result)
+    }
+
+    /// Open a fresh TCP stream and perform a real live SASL handshake over it
+    /// via `AuthManager::authenticate_live`; the stream is only ever handed to
+    /// `write_stanza`/`pump_inbound_stanzas` once that handshake has actually
+    /// completed. If the server doesn't finish stream negotiation within
+    /// `Config::auth_timeout`, the attempt fails outright -- there's no
+    /// simulated-authentication fallback that would leave the client
+    /// believing it's connected over a socket the server never confirmed.
+    /// Used by `connect` and by `reconnect`'s full-reauthentication fallback.
+    async fn bind_live_stream(&mut self, credentials: &Credentials) -> Result<AuthResult> {
+        let mut stream = Self::open_stream(&self.config).await?;
+
+        let mut auth_manager = self.auth_manager.lock().await;
+        let auth_result = match tokio::time::timeout(
+            Duration::from_secs(self.config.auth_timeout),
+            auth_manager.authenticate_live(credentials, &mut stream),
+        )
+        .await
+        {
+            Ok(result) => result?,
+            Err(_) => {
+                return Err(OpenFireError::TimeoutError {
+                    seconds: self.config.auth_timeout,
+                });
+            }
+        };
+        drop(auth_manager);
+
+        self.finish_binding(stream, auth_result, Vec::new()).await
+    }
+
+    /// Shared tail of every live-connect path: reject an unsuccessful
+    /// `auth_result` before the stream is ever handed to
+    /// `write_stanza`/`pump_inbound_stanzas`, otherwise split the stream,
+    /// spawn the inbound pump, and mark the client connected. `pending_bytes`
+    /// seeds the pump's read buffer with anything already read off the
+    /// socket during authentication (e.g. stanzas a server replays right
+    /// after a successful `<resumed/>`) so they aren't silently dropped.
+    /// Used by `bind_live_stream` (password/SCRAM), `connect_sso`
+    /// (OAUTHBEARER), and `reconnect_inner`'s resume path, which differ only
+    /// in how they obtain `auth_result` and `pending_bytes`.
+    async fn finish_binding(&mut self, stream: TcpStream, auth_result: AuthResult, pending_bytes: Vec<u8>) -> Result<AuthResult> {
         if !auth_result.success {
             return Err(OpenFireError::AuthenticationFailed {
                 message: auth_result.message,
             });
         }
-        
+
+        let (read_half, write_half) = stream.into_split();
+        self.write_half = Some(Arc::new(Mutex::new(write_half)));
+
+        // The pump is what resolves `send_iq` waiters, so it runs even when no
+        // event callback is set; its events are then drained and discarded.
+        let tx = match self.event_tx.clone() {
+            Some(tx) => tx,
+            None => {
+                let (tx, mut rx) = mpsc::unbounded_channel();
+                tokio::spawn(async move { while rx.recv().await.is_some() {} });
+                tx
+            }
+        };
+        tokio::spawn(Self::pump_inbound_stanzas(
+            read_half,
+            pending_bytes,
+            tx,
+            self.archive.clone(),
+            self.pending_iqs.clone(),
+            self.contacts.clone(),
+            self.chat_rooms.clone(),
+            self.auth_manager.clone(),
+            self.mam_queries.clone(),
+        ));
+
         self.is_connected = true;
-        
-        // Set initial presence
-        let jid = auth_result.full_jid.unwrap_or_else(|| "unknown@localhost".to_string());
+
+        let jid = auth_result.full_jid.clone().unwrap_or_else(|| "unknown@localhost".to_string());
         self.current_presence = Some(Presence::new(jid, PresenceStatus::Available));
-        
-        info!("Successfully connected to OpenFire server");
-        self.emit_event(XmppEvent::ConnectionStateChanged("connected".to_string())).await;
-        
-        Ok(())
+
+        Ok(auth_result)
     }
-    
+
+    /// Read inbound XMPP bytes off the wire, parse complete stanzas, archive
+    /// any messages, resolve any IQ responses waiters are blocked on, apply
+    /// roster/presence updates to `contacts`, bump the stream-management
+    /// stanza count `resume_live` will next report, and forward everything
+    /// else as an `XmppEvent` until the connection closes. `buffer` starts
+    /// out seeded with `finish_binding`'s `pending_bytes`, not empty.
+    async fn pump_inbound_stanzas(
+        mut read_half: OwnedReadHalf,
+        mut buffer: Vec<u8>,
+        tx: mpsc::UnboundedSender<XmppEvent>,
+        archive: Option<Arc<MessageArchive>>,
+        pending_iqs: Arc<Mutex<HashMap<String, oneshot::Sender<String>>>>,
+        contacts: Arc<Mutex<HashMap<String, Contact>>>,
+        chat_rooms: Arc<Mutex<HashMap<String, ChatRoom>>>,
+        auth_manager: Arc<Mutex<AuthManager>>,
+        mam_queries: Arc<Mutex<HashMap<String, String>>>,
+    ) {
+        let mut chunk = [0u8; 4096];
+
+        loop {
+            // Drain whatever's already in `buffer` before blocking on the next
+            // read -- on the very first iteration this is `pending_bytes`
+            // (e.g. stanzas a server replayed right after `<resumed/>`), which
+            // would otherwise sit unprocessed until the next socket read.
+            match stanza::drain_stanzas(&mut buffer) {
+                Ok(events) => {
+                    for event in events {
+                        auth_manager.lock().await.record_inbound_stanza();
+
+                        if let (XmppEvent::MessageReceived(message), Some(archive)) = (&event, &archive) {
+                            if let Err(e) = archive.store(&bare_jid(&message.from), message) {
+                                error!("Failed to archive inbound message: {}", e);
+                            }
+                        }
+
+                        if let XmppEvent::MamResult { query_id, message } = &event {
+                            if let Some(archive) = &archive {
+                                let conversation_jid = mam_queries.lock().await.get(query_id).cloned();
+                                if let Some(conversation_jid) = conversation_jid {
+                                    if let Err(e) = archive.store(&conversation_jid, message) {
+                                        error!("Failed to archive MAM result message: {}", e);
+                                    }
+                                }
+                            }
+                        }
+
+                        if let XmppEvent::IqReceived { id, xml } = &event {
+                            if let Some(waiter) = pending_iqs.lock().await.remove(id) {
+                                let _ = waiter.send(xml.clone());
+                                continue;
+                            }
+                        }
+
+                        if let XmppEvent::SubscriptionPresence { jid, kind } = &event {
+                            let bare = bare_jid(jid);
+                            let mut contacts = contacts.lock().await;
+                            let contact = contacts.entry(bare.clone()).or_insert_with(|| Contact {
+                                jid: bare,
+                                name: None,
+                                subscription: "none".to_string(),
+                                groups: Vec::new(),
+                                presence: None,
+                            });
+                            contact.subscription = apply_subscription_transition(&contact.subscription, kind);
+                            let updated = contact.clone();
+                            drop(contacts);
+                            let _ = tx.send(XmppEvent::ContactUpdated(updated));
+                            continue;
+                        }
+
+                        if let XmppEvent::PresenceUpdated(presence) = &event {
+                            if let Some(contact) = contacts.lock().await.get_mut(&bare_jid(&presence.jid)) {
+                                contact.presence = Some(presence.clone());
+                            }
+                        }
+
+                        if let XmppEvent::MessageReceived(message) = &event {
+                            if message.message_type == MessageType::GroupChat {
+                                if let Some(subject) = &message.subject {
+                                    if let Some(room) =
+                                        chat_rooms.lock().await.get_mut(&bare_jid(&message.from))
+                                    {
+                                        room.subject = Some(subject.clone());
+                                    }
+                                }
+                            }
+                        }
+
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(XmppEvent::Error(e.to_string()));
+                }
+            }
+
+            match read_half.read(&mut chunk).await {
+                Ok(0) => {
+                    let _ = tx.send(XmppEvent::ConnectionStateChanged("disconnected".to_string()));
+                    break;
+                }
+                Ok(n) => {
+                    buffer.extend_from_slice(&chunk[..n]);
+                }
+                Err(e) => {
+                    let _ = tx.send(XmppEvent::Error(format!("XMPP stream read error: {}", e)));
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Write a serialized stanza to the open XMPP stream
+    async fn write_stanza(&self, xml: &str) -> Result<()> {
+        let Some(write_half) = &self.write_half else {
+            return Err(OpenFireError::ConnectionError {
+                message: "No XMPP stream is open".to_string(),
+            });
+        };
+
+        let mut stream = write_half.lock().await;
+        stream
+            .write_all(xml.as_bytes())
+            .await
+            .map_err(|e| OpenFireError::ConnectionError {
+                message: format!("Failed to write stanza: {}", e),
+            })
+    }
+
     /// Disconnect from the OpenFire server
     pub async fn disconnect(&mut self) -> Result<()> {
         info!("Disconnecting from OpenFire server");
-        
+
+        if let Some(write_half) = self.write_half.take() {
+            let mut stream = write_half.lock().await;
+            let _ = stream.write_all(b"</stream:stream>").await;
+        }
+
         let mut auth_manager = self.auth_manager.lock().await;
         auth_manager.disconnect().await?;
-        
+
         self.is_connected = false;
         self.current_presence = None;
-        
+
         info!("Disconnected from OpenFire server");
         self.emit_event(XmppEvent::ConnectionStateChanged("disconnected".to_string())).await;
-        
+
         Ok(())
     }
     
@@ -225,15 +731,18 @@ impl OpenFireClient {
             .unwrap_or_else(|| "unknown@localhost".to_string());
             
         let message = Message::new_chat(from, to.to_string(), body.to_string());
-        
+
         info!("Sending message to {}: {}", to, body);
-        
-        // In a real implementation, this would send the message via XMPP
-        // For now, we'll simulate the sending process
-        tokio::time::sleep(Duration::from_millis(50)).await;
-        
+        self.write_stanza(&message.to_xml()).await?;
+
+        if let Some(archive) = &self.archive {
+            if let Err(e) = archive.store(&bare_jid(to), &message) {
+                error!("Failed to archive outbound message: {}", e);
+            }
+        }
+
         debug!("Message sent successfully: {}", message.id);
-        
+
         Ok(message.id)
     }
     
@@ -245,7 +754,7 @@ impl OpenFireClient {
             });
         }
         
-        if !self.chat_rooms.contains_key(room_jid) {
+        if !self.chat_rooms.lock().await.contains_key(room_jid) {
             return Err(OpenFireError::XmppProtocolError {
                 message: format!("Not joined to room: {}", room_jid),
             });
@@ -257,17 +766,224 @@ impl OpenFireClient {
             .unwrap_or_else(|| "unknown@localhost".to_string());
             
         let message = Message::new_group_chat(from, room_jid.to_string(), body.to_string());
-        
+
         info!("Sending group message to {}: {}", room_jid, body);
-        
-        // Simulate sending group message
-        tokio::time::sleep(Duration::from_millis(50)).await;
-        
+        self.write_stanza(&message.to_xml()).await?;
+
+        // The MUC service reflects every groupchat message back to its sender
+        // with the server-assigned timestamp; that echo (handled in
+        // `pump_inbound_stanzas`) is what gets archived, so we don't double-store here.
+
         debug!("Group message sent successfully: {}", message.id);
-        
+
         Ok(message.id)
     }
-    
+
+    /// Fetch archived history for `jid` (a room or peer), newest-bounded by
+    /// `before` for paging, returned oldest first
+    pub fn get_history(&self, jid: &str, limit: usize, before: Option<u64>) -> Result<Vec<Message>> {
+        let Some(archive) = &self.archive else {
+            return Err(OpenFireError::ConfigError {
+                message: "No message archive is configured".to_string(),
+            });
+        };
+
+        archive.get_history(&bare_jid(jid), limit, before)
+    }
+
+    /// Request history for `jid` (a room or peer, as in `get_history`) from
+    /// its XEP-0313 MAM archive: sends a real `<query xmlns='urn:xmpp:mam:2'>`
+    /// IQ and waits for the server's `<fin/>`. Each `<result/>` the server
+    /// streams back as a separate `<message>` before that `<fin/>` arrives is
+    /// archived locally by `pump_inbound_stanzas` (see `XmppEvent::MamResult`)
+    /// and forwarded to the event callback the same way a live message would
+    /// be -- call `get_history` afterward to read the paged-in results back.
+    /// Returns whether the server reported the result set as `complete`; the
+    /// query only ever requests the newest `limit` messages, so an
+    /// incomplete result set's earlier history isn't reachable through this
+    /// call yet (`mam::query_payload` has no RSM `before`/`after` cursor).
+    pub async fn fetch_remote_history(&self, jid: &str, limit: u32) -> Result<bool> {
+        if !self.is_connected {
+            return Err(OpenFireError::ConnectionError {
+                message: "Not connected to server".to_string(),
+            });
+        }
+
+        let bare_target = bare_jid(jid);
+        let is_room = self.chat_rooms.lock().await.contains_key(&bare_target);
+        let (archive_jid, with) = if is_room {
+            (bare_target.clone(), None)
+        } else {
+            let own_jid = self
+                .current_presence
+                .as_ref()
+                .map(|p| bare_jid(&p.jid))
+                .unwrap_or_else(|| "unknown@localhost".to_string());
+            (own_jid, Some(bare_target.as_str()))
+        };
+
+        let query_id = generate_iq_id();
+        self.mam_queries.lock().await.insert(query_id.clone(), bare_target.clone());
+
+        let payload = mam::query_payload(&query_id, with, limit);
+        let result = self.send_iq(&archive_jid, "set", &payload).await;
+
+        self.mam_queries.lock().await.remove(&query_id);
+
+        let result_xml = result?;
+        if let Some(condition) = discovery::iq_error_condition(&result_xml) {
+            return Err(OpenFireError::XmppProtocolError {
+                message: format!("MAM query for {} was rejected by the server: {}", jid, condition),
+            });
+        }
+
+        Ok(mam::parse_fin(&result_xml).complete)
+    }
+
+    /// Send an IQ stanza to `to` and wait (bounded by `Config::connection_timeout`)
+    /// for the correlated `<iq>` response, returning its raw XML
+    async fn send_iq(&self, to: &str, iq_type: &str, payload_xml: &str) -> Result<String> {
+        if !self.is_connected {
+            return Err(OpenFireError::ConnectionError {
+                message: "Not connected to server".to_string(),
+            });
+        }
+
+        let from = self.current_presence
+            .as_ref()
+            .map(|p| p.jid.clone())
+            .unwrap_or_else(|| "unknown@localhost".to_string());
+        let id = generate_iq_id();
+        let iq_xml = format!(
+            "<iq type='{}' id='{}' from='{}' to='{}'>{}</iq>",
+            iq_type, id, from, to, payload_xml
+        );
+
+        let (response_tx, response_rx) = oneshot::channel();
+        self.pending_iqs.lock().await.insert(id.clone(), response_tx);
+
+        self.write_stanza(&iq_xml).await?;
+
+        match tokio::time::timeout(Duration::from_secs(self.config.connection_timeout), response_rx).await {
+            Ok(Ok(xml)) => Ok(xml),
+            Ok(Err(_)) => Err(OpenFireError::ConnectionError {
+                message: "IQ response channel closed before a reply arrived".to_string(),
+            }),
+            Err(_) => {
+                self.pending_iqs.lock().await.remove(&id);
+                Err(OpenFireError::TimeoutError {
+                    seconds: self.config.connection_timeout,
+                })
+            }
+        }
+    }
+
+    /// Find the server's HTTP file upload (XEP-0363) component by walking
+    /// `Config::domain`'s disco#items children and checking each one's
+    /// disco#info for the upload namespace. Returns the component's JID and
+    /// its advertised max upload size, if any.
+    async fn discover_upload_service(&self) -> Result<(String, Option<u64>)> {
+        let items_xml = self
+            .send_iq(&self.config.domain, "get", discovery::disco_items_payload())
+            .await?;
+
+        for item in discovery::parse_disco_items(&items_xml) {
+            let info_xml = match self.send_iq(&item.jid, "get", discovery::disco_info_payload()).await {
+                Ok(xml) => xml,
+                Err(e) => {
+                    debug!("disco#info failed for {}: {}", item.jid, e);
+                    continue;
+                }
+            };
+
+            if let Some(max_size) = upload::parse_upload_service_info(&info_xml) {
+                return Ok((item.jid, max_size));
+            }
+        }
+
+        Err(OpenFireError::XmppProtocolError {
+            message: "No HTTP file upload service (XEP-0363) advertised by the server".to_string(),
+        })
+    }
+
+    /// Request an upload slot for a file from the server's HTTP-upload
+    /// component. The returned slot's `put_url` is where the file bytes
+    /// should be PUT; its `get_url` is what to share with the recipient.
+    pub async fn request_upload_slot(&self, filename: &str, size: u64, content_type: &str) -> Result<UploadSlot> {
+        let (upload_service, max_size) = self.discover_upload_service().await?;
+
+        if let Some(max_size) = max_size {
+            if size > max_size {
+                return Err(OpenFireError::XmppProtocolError {
+                    message: format!(
+                        "File size {} exceeds the server's maximum upload size of {} bytes",
+                        size, max_size
+                    ),
+                });
+            }
+        }
+
+        let slot_xml = self
+            .send_iq(&upload_service, "get", &upload::slot_request_payload(filename, size, content_type))
+            .await?;
+
+        upload::parse_upload_slot(&slot_xml)
+    }
+
+    /// Share a file in a chat by requesting an upload slot, PUTting its bytes
+    /// to the slot's URL, then sending a chat message whose body is the
+    /// resulting GET URL with an out-of-band-data annotation (XEP-0066)
+    pub async fn send_file(&self, to: &str, path: &Path) -> Result<String> {
+        let bytes = tokio::fs::read(path).await?;
+
+        let filename = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("upload.bin");
+        let content_type = guess_content_type(path);
+
+        let slot = self.request_upload_slot(filename, bytes.len() as u64, content_type).await?;
+
+        let http_client = reqwest::Client::new();
+        let mut request = http_client.put(&slot.put_url).body(bytes);
+        for (name, value) in &slot.put_headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request.send().await.map_err(|e| OpenFireError::ConnectionError {
+            message: format!("Failed to upload file to {}: {}", slot.put_url, e),
+        })?;
+
+        if !response.status().is_success() {
+            return Err(OpenFireError::XmppProtocolError {
+                message: format!("Upload server rejected PUT with status {}", response.status()),
+            });
+        }
+
+        let from = self.current_presence
+            .as_ref()
+            .map(|p| p.jid.clone())
+            .unwrap_or_else(|| "unknown@localhost".to_string());
+        let message = Message::new_chat(from, to.to_string(), slot.get_url.clone());
+        let xml = message.to_xml().replace(
+            "</message>",
+            &format!(
+                "<x xmlns='jabber:x:oob'><url>{}</url></x></message>",
+                escape_xml(&slot.get_url)
+            ),
+        );
+
+        self.write_stanza(&xml).await?;
+
+        if let Some(archive) = &self.archive {
+            if let Err(e) = archive.store(&bare_jid(to), &message) {
+                error!("Failed to archive outbound file share: {}", e);
+            }
+        }
+
+        Ok(message.id)
+    }
+
     /// Update presence status
     pub async fn set_presence(&mut self, status: PresenceStatus, message: Option<String>) -> Result<()> {
         if !self.is_connected {
@@ -287,10 +1003,8 @@ impl OpenFireClient {
         }
         
         info!("Setting presence to: {:?}", presence.status);
-        
-        // Simulate presence update
-        tokio::time::sleep(Duration::from_millis(30)).await;
-        
+        self.write_stanza(&presence.to_xml()).await?;
+
         self.current_presence = Some(presence.clone());
         self.emit_event(XmppEvent::PresenceUpdated(presence)).await;
         
@@ -302,19 +1016,30 @@ impl OpenFireClient {
         self.current_presence.as_ref()
     }
     
-    /// Join a chat room
-    pub async fn join_room(&mut self, room_jid: &str, nickname: &str) -> Result<()> {
+    /// Join a chat room. If `bookmark` is `Some(autojoin)`, the room is also
+    /// persisted as a `urn:xmpp:bookmarks:1` PubSub bookmark (see
+    /// `load_bookmarks`/`autojoin_bookmarked_rooms`) so a future session can
+    /// find and optionally rejoin it; bookmark persistence failures are
+    /// logged but don't fail the join itself.
+    pub async fn join_room(&mut self, room_jid: &str, nickname: &str, bookmark: Option<bool>) -> Result<()> {
         if !self.is_connected {
             return Err(OpenFireError::ConnectionError {
                 message: "Not connected to server".to_string(),
             });
         }
-        
+
         info!("Joining chat room: {} as {}", room_jid, nickname);
-        
-        // Simulate joining room
-        tokio::time::sleep(Duration::from_millis(100)).await;
-        
+
+        // XEP-0045: joining is a directed presence to the room/nick occupant
+        // JID, carrying the MUC namespace so the service treats it as a join
+        // rather than a plain presence update
+        self.write_stanza(&format!(
+            "<presence to='{}/{}'><x xmlns='http://jabber.org/protocol/muc'/></presence>",
+            escape_xml(room_jid),
+            escape_xml(nickname)
+        ))
+        .await?;
+
         let chat_room = ChatRoom {
             jid: room_jid.to_string(),
             name: room_jid.split('@').next().unwrap_or(room_jid).to_string(),
@@ -323,14 +1048,31 @@ impl OpenFireClient {
             participants: vec![nickname.to_string()],
             joined: true,
         };
-        
-        self.chat_rooms.insert(room_jid.to_string(), chat_room);
-        
+
+        self.chat_rooms.lock().await.insert(room_jid.to_string(), chat_room);
+
         info!("Successfully joined room: {}", room_jid);
-        
+
+        if let Some(archive) = &self.archive {
+            match archive.get_history(&bare_jid(room_jid), DEFAULT_HISTORY_REPLAY_LIMIT, None) {
+                Ok(history) => {
+                    for message in history {
+                        self.emit_event(XmppEvent::MessageReceived(message)).await;
+                    }
+                }
+                Err(e) => warn!("Failed to replay archived history for {}: {}", room_jid, e),
+            }
+        }
+
+        if let Some(autojoin) = bookmark {
+            if let Err(e) = self.publish_bookmark(room_jid, nickname, autojoin).await {
+                warn!("Failed to persist bookmark for {}: {}", room_jid, e);
+            }
+        }
+
         Ok(())
     }
-    
+
     /// Leave a chat room
     pub async fn leave_room(&mut self, room_jid: &str) -> Result<()> {
         if !self.is_connected {
@@ -338,22 +1080,117 @@ impl OpenFireClient {
                 message: "Not connected to server".to_string(),
             });
         }
-        
+
         info!("Leaving chat room: {}", room_jid);
-        
-        // Simulate leaving room
-        tokio::time::sleep(Duration::from_millis(50)).await;
-        
-        self.chat_rooms.remove(room_jid);
-        
+
+        // XEP-0045: leaving is unavailable presence from the same occupant
+        // JID we joined under; fall back to the bare room jid if we never
+        // recorded a nickname for it (e.g. `leave_room` called without a
+        // matching `join_room` in this session)
+        let nickname = self
+            .chat_rooms
+            .lock()
+            .await
+            .get(room_jid)
+            .and_then(|room| room.participants.first().cloned());
+        let occupant_jid = match nickname {
+            Some(nickname) => format!("{}/{}", escape_xml(room_jid), escape_xml(&nickname)),
+            None => escape_xml(room_jid),
+        };
+        self.write_stanza(&format!("<presence type='unavailable' to='{}'/>", occupant_jid))
+            .await?;
+
+        self.chat_rooms.lock().await.remove(room_jid);
+
         info!("Successfully left room: {}", room_jid);
-        
+
         Ok(())
     }
-    
+
     /// Get list of joined chat rooms
-    pub fn get_chat_rooms(&self) -> Vec<&ChatRoom> {
-        self.chat_rooms.values().collect()
+    pub async fn get_chat_rooms(&self) -> Vec<ChatRoom> {
+        self.chat_rooms.lock().await.values().cloned().collect()
+    }
+
+    /// Store `room_jid` as a `urn:xmpp:bookmarks:1` PubSub bookmark (XEP-0402),
+    /// carrying its currently-known subject (if any) so a later
+    /// `autojoin_bookmarked_rooms` call can restore the topic
+    async fn publish_bookmark(&self, room_jid: &str, nickname: &str, autojoin: bool) -> Result<()> {
+        let subject = self.chat_rooms.lock().await.get(room_jid).and_then(|room| room.subject.clone());
+        let bookmark = bookmarks::Conference {
+            jid: room_jid.to_string(),
+            name: None,
+            autojoin,
+            nickname: Some(nickname.to_string()),
+            subject,
+        };
+
+        self.send_iq(&self.own_bare_jid(), "set", &bookmarks::publish_payload(&bookmark))
+            .await?;
+        Ok(())
+    }
+
+    /// Our own bare JID, used as the target of self-addressed PubSub IQs
+    /// (bookmarks live on the user's own PEP node)
+    fn own_bare_jid(&self) -> String {
+        self.current_presence
+            .as_ref()
+            .map(|p| bare_jid(&p.jid))
+            .unwrap_or_else(|| "unknown@localhost".to_string())
+    }
+
+    /// Fetch every room bookmarked via PubSub (`urn:xmpp:bookmarks:1`)
+    async fn fetch_bookmarks(&self) -> Result<Vec<bookmarks::Conference>> {
+        let xml = self
+            .send_iq(&self.own_bare_jid(), "get", &bookmarks::items_request_payload())
+            .await?;
+        bookmarks::parse_bookmarks(&xml)
+    }
+
+    /// Load every bookmarked room, regardless of its autojoin flag, as
+    /// not-yet-joined `ChatRoom`s (e.g. for a "saved rooms" UI list)
+    pub async fn load_bookmarks(&self) -> Result<Vec<ChatRoom>> {
+        let conferences = self.fetch_bookmarks().await?;
+
+        Ok(conferences
+            .into_iter()
+            .map(|bookmark| ChatRoom {
+                name: bookmark
+                    .name
+                    .unwrap_or_else(|| bookmark.jid.split('@').next().unwrap_or(&bookmark.jid).to_string()),
+                jid: bookmark.jid,
+                description: None,
+                subject: bookmark.subject,
+                participants: Vec::new(),
+                joined: false,
+            })
+            .collect())
+    }
+
+    /// Rejoin every bookmarked room whose `autojoin` flag is set, restoring
+    /// each room's last-known subject instead of leaving it `None`. Call
+    /// after `connect` (and after presence is set, since joining needs a JID
+    /// to join as). Rooms that fail to (re)join are logged and skipped so one
+    /// bad bookmark doesn't block the rest.
+    pub async fn autojoin_bookmarked_rooms(&mut self) -> Result<Vec<String>> {
+        let conferences = self.fetch_bookmarks().await?;
+        let mut joined = Vec::new();
+
+        for bookmark in conferences.into_iter().filter(|bookmark| bookmark.autojoin) {
+            let nickname = bookmark.nickname.clone().unwrap_or_else(|| "user".to_string());
+            if let Err(e) = self.join_room(&bookmark.jid, &nickname, None).await {
+                warn!("Failed to autojoin bookmarked room {}: {}", bookmark.jid, e);
+                continue;
+            }
+
+            if let Some(room) = self.chat_rooms.lock().await.get_mut(&bookmark.jid) {
+                room.subject = bookmark.subject;
+            }
+
+            joined.push(bookmark.jid);
+        }
+
+        Ok(joined)
     }
     
     /// Add a contact to the roster
@@ -365,26 +1202,43 @@ impl OpenFireClient {
         }
         
         info!("Adding contact: {}", jid);
-        
-        // Simulate adding contact
-        tokio::time::sleep(Duration::from_millis(50)).await;
-        
+
+        // RFC 6121 roster add: an `<iq type='set'>` roster set, addressed to
+        // ourselves like the bookmark PubSub IQs above
+        let mut item = format!("<item jid='{}'", escape_xml(jid));
+        if let Some(name) = &name {
+            item.push_str(&format!(" name='{}'", escape_xml(name)));
+        }
+        item.push('>');
+        for group in &groups {
+            item.push_str(&format!("<group>{}</group>", escape_xml(group)));
+        }
+        item.push_str("</item>");
+        let payload = format!("<query xmlns='jabber:iq:roster'>{}</query>", item);
+
+        let result_xml = self.send_iq(&self.own_bare_jid(), "set", &payload).await?;
+        if let Some(condition) = discovery::iq_error_condition(&result_xml) {
+            return Err(OpenFireError::XmppProtocolError {
+                message: format!("Roster add for {} was rejected by the server: {}", jid, condition),
+            });
+        }
+
         let contact = Contact {
-            jid: jid.to_string(),
+            jid: bare_jid(jid),
             name,
             subscription: "none".to_string(),
             groups,
             presence: None,
         };
-        
-        self.contacts.insert(jid.to_string(), contact.clone());
+
+        self.contacts.lock().await.insert(contact.jid.clone(), contact.clone());
         self.emit_event(XmppEvent::ContactUpdated(contact)).await;
-        
+
         info!("Successfully added contact: {}", jid);
-        
+
         Ok(())
     }
-    
+
     /// Remove a contact from the roster
     pub async fn remove_contact(&mut self, jid: &str) -> Result<()> {
         if !self.is_connected {
@@ -392,22 +1246,106 @@ impl OpenFireClient {
                 message: "Not connected to server".to_string(),
             });
         }
-        
+
         info!("Removing contact: {}", jid);
-        
-        // Simulate removing contact
-        tokio::time::sleep(Duration::from_millis(50)).await;
-        
-        self.contacts.remove(jid);
-        
+
+        // RFC 6121 roster remove: a roster set with subscription='remove'
+        // tells the server to delete the item and tear down any subscription
+        let payload = format!(
+            "<query xmlns='jabber:iq:roster'><item jid='{}' subscription='remove'/></query>",
+            escape_xml(jid)
+        );
+        let result_xml = self.send_iq(&self.own_bare_jid(), "set", &payload).await?;
+        if let Some(condition) = discovery::iq_error_condition(&result_xml) {
+            return Err(OpenFireError::XmppProtocolError {
+                message: format!("Roster remove for {} was rejected by the server: {}", jid, condition),
+            });
+        }
+
+        self.contacts.lock().await.remove(&bare_jid(jid));
+
         info!("Successfully removed contact: {}", jid);
-        
+
         Ok(())
     }
-    
+
     /// Get list of contacts
-    pub fn get_contacts(&self) -> Vec<&Contact> {
-        self.contacts.values().collect()
+    pub async fn get_contacts(&self) -> Vec<Contact> {
+        self.contacts.lock().await.values().cloned().collect()
+    }
+
+    /// Ask `jid` to share their presence with us (RFC 6121 `<presence
+    /// type='subscribe'/>`). Nothing changes locally until they approve,
+    /// which arrives as an inbound `subscribed` presence (see
+    /// `pump_inbound_stanzas`) and updates `Contact.subscription` for us.
+    pub async fn request_subscription(&self, jid: &str) -> Result<()> {
+        if !self.is_connected {
+            return Err(OpenFireError::ConnectionError {
+                message: "Not connected to server".to_string(),
+            });
+        }
+
+        info!("Requesting presence subscription to: {}", jid);
+        self.write_stanza(&format!("<presence type='subscribe' to='{}'/>", escape_xml(jid)))
+            .await
+    }
+
+    /// Approve an incoming subscription request from `jid` (RFC 6121
+    /// `<presence type='subscribed'/>`), granting them our presence
+    pub async fn approve_subscription(&self, jid: &str) -> Result<()> {
+        if !self.is_connected {
+            return Err(OpenFireError::ConnectionError {
+                message: "Not connected to server".to_string(),
+            });
+        }
+
+        info!("Approving presence subscription for: {}", jid);
+        self.write_stanza(&format!("<presence type='subscribed' to='{}'/>", escape_xml(jid)))
+            .await?;
+
+        // Sending `subscribed` grants the peer our presence, i.e. sets *our*
+        // `from` bit (they can now see us) — the opposite of the `to` bit an
+        // inbound `subscribed` presence sets in `apply_subscription_transition`.
+        // The peer may not be in our roster yet (e.g. we're approving their
+        // `SubscriptionRequest` without having called `add_contact` first).
+        let bare = bare_jid(jid);
+        let mut contacts = self.contacts.lock().await;
+        let contact = contacts.entry(bare.clone()).or_insert_with(|| Contact {
+            jid: bare,
+            name: None,
+            subscription: "none".to_string(),
+            groups: Vec::new(),
+            presence: None,
+        });
+        contact.subscription = match contact.subscription.as_str() {
+            "to" | "both" => "both",
+            _ => "from",
+        }
+        .to_string();
+
+        Ok(())
+    }
+
+    /// Sever presence sharing with `jid` in both directions: cancel our
+    /// subscription to their presence and revoke their subscription to ours
+    /// (RFC 6121 `<presence type='unsubscribe'/>` and `type='unsubscribed'/>`)
+    pub async fn unsubscribe(&self, jid: &str) -> Result<()> {
+        if !self.is_connected {
+            return Err(OpenFireError::ConnectionError {
+                message: "Not connected to server".to_string(),
+            });
+        }
+
+        info!("Unsubscribing from presence with: {}", jid);
+        let escaped = escape_xml(jid);
+        self.write_stanza(&format!("<presence type='unsubscribe' to='{}'/>", escaped)).await?;
+        self.write_stanza(&format!("<presence type='unsubscribed' to='{}'/>", escaped)).await?;
+
+        if let Some(contact) = self.contacts.lock().await.get_mut(&bare_jid(jid)) {
+            contact.subscription = "none".to_string();
+        }
+
+        Ok(())
     }
     
     /// Set event callback for receiving events
@@ -431,25 +1369,66 @@ impl OpenFireClient {
         }
     }
     
-    /// Get server information
-    pub async fn get_server_info(&self) -> Result<HashMap<String, String>> {
-        if !self.is_connected {
-            return Err(OpenFireError::ConnectionError {
-                message: "Not connected to server".to_string(),
+    /// Query `jid`'s disco#info (XEP-0030) identities and features, caching
+    /// the result so repeat callers (ours or another subsystem's) skip the
+    /// round trip; pass `Config::domain` for the server's own info.
+    pub async fn get_server_info(&self, jid: &str) -> Result<ServerInfo> {
+        if let Some(cached) = self.feature_cache.lock().await.get(jid) {
+            return Ok(cached.clone());
+        }
+
+        let info_xml = self.send_iq(jid, "get", discovery::disco_info_payload()).await?;
+        if let Some(condition) = discovery::iq_error_condition(&info_xml) {
+            return Err(OpenFireError::XmppProtocolError {
+                message: format!("disco#info on {} returned an error: {}", jid, condition),
             });
         }
-        
-        // Simulate getting server info
-        tokio::time::sleep(Duration::from_millis(100)).await;
-        
-        let mut info = HashMap::new();
-        info.insert("server".to_string(), self.config.server.clone());
-        info.insert("domain".to_string(), self.config.domain.clone());
-        info.insert("port".to_string(), self.config.port.to_string());
-        info.insert("version".to_string(), "OpenFire 4.7.0".to_string());
-        
+
+        let info = discovery::parse_disco_info(&info_xml)?;
+        self.feature_cache.lock().await.insert(jid.to_string(), info.clone());
         Ok(info)
     }
+
+    /// Look up an already-cached `get_server_info` result without a round
+    /// trip, for subsystems that only want to gate on features if we already
+    /// know them (e.g. "advertise file sharing only if previously discovered")
+    pub async fn cached_server_info(&self, jid: &str) -> Option<ServerInfo> {
+        self.feature_cache.lock().await.get(jid).cloned()
+    }
+
+    /// Enumerate the components `jid` advertises via disco#items (XEP-0030),
+    /// e.g. the server domain's MUC service, file-upload service, and PubSub node
+    pub async fn disco_items(&self, jid: &str) -> Result<Vec<DiscoItem>> {
+        let items_xml = self.send_iq(jid, "get", discovery::disco_items_payload()).await?;
+        if let Some(condition) = discovery::iq_error_condition(&items_xml) {
+            return Err(OpenFireError::XmppProtocolError {
+                message: format!("disco#items on {} returned an error: {}", jid, condition),
+            });
+        }
+
+        Ok(discovery::parse_disco_items(&items_xml))
+    }
+
+    /// Query a specific MUC room's advertised features and occupant count,
+    /// returning `OpenFireError::RoomNotFound` if the server answers with an
+    /// `item-not-found` disco#info error (the room doesn't exist)
+    pub async fn room_info(&self, room_jid: &str) -> Result<RoomInfo> {
+        let info_xml = self.send_iq(room_jid, "get", discovery::disco_info_payload()).await?;
+
+        if let Some(condition) = discovery::iq_error_condition(&info_xml) {
+            return if condition == "item-not-found" {
+                Err(OpenFireError::RoomNotFound {
+                    jid: room_jid.to_string(),
+                })
+            } else {
+                Err(OpenFireError::XmppProtocolError {
+                    message: format!("disco#info on room {} returned an error: {}", room_jid, condition),
+                })
+            };
+        }
+
+        discovery::parse_room_info(room_jid, &info_xml)
+    }
 }
 
 // Utility functions
@@ -458,13 +1437,40 @@ fn generate_message_id() -> String {
     format!("msg_{:x}", timestamp)
 }
 
-fn current_timestamp() -> u64 {
+/// A process-wide counter appended to generated IQ ids so that two IQs sent
+/// within the same second (`generate_message_id`'s resolution) never collide
+/// in `OpenFireClient::pending_iqs` and get each other's responses
+static IQ_ID_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+fn generate_iq_id() -> String {
+    let sequence = IQ_ID_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    format!("iq_{:x}_{:x}", current_timestamp(), sequence)
+}
+
+pub(crate) fn current_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs()
 }
 
+/// Guess a file's MIME type from its extension for the HTTP upload `content-type`
+/// field (XEP-0363 doesn't require precision here; the server mostly just stores it)
+fn guess_content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "mp4" => "video/mp4",
+        "mp3" => "audio/mpeg",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -502,19 +1508,628 @@ mod tests {
         assert_eq!(presence.priority, 5);
     }
 
+    /// Complete the real handshake `AuthManager::authenticate_live` drives:
+    /// consume the client's `<stream:stream>` open tag, advertise PLAIN as the
+    /// only mechanism, then immediately approve whatever initial response
+    /// arrives. Every fake server below calls this first so `connect()`
+    /// actually binds before the behavior under test takes over.
+    async fn complete_live_handshake(socket: &mut tokio::net::TcpStream) {
+        let mut buf = [0u8; 4096];
+        let _ = socket.read(&mut buf).await;
+        let _ = socket
+            .write_all(
+                b"<stream:features><mechanisms xmlns='urn:ietf:params:xml:ns:xmpp-sasl'>\
+                  <mechanism>PLAIN</mechanism></mechanisms></stream:features>",
+            )
+            .await;
+        let _ = socket.read(&mut buf).await;
+        let _ = socket
+            .write_all(b"<success xmlns='urn:ietf:params:xml:ns:xmpp-sasl'/>")
+            .await;
+    }
+
+    /// Bind a loopback listener that completes the live handshake, then
+    /// answers every IQ it receives with an empty result and ignores any
+    /// other stanza, standing in for a real OpenFire server's XMPP socket
+    async fn spawn_fake_server() -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                complete_live_handshake(&mut socket).await;
+                let mut buffer = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let n = match socket.read(&mut chunk).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n,
+                    };
+                    buffer.extend_from_slice(&chunk[..n]);
+
+                    let text = String::from_utf8_lossy(&buffer).into_owned();
+                    let Some((stanza_xml, consumed)) = stanza::next_complete_stanza(&text) else {
+                        continue;
+                    };
+                    buffer.drain(..consumed.min(buffer.len()));
+
+                    if stanza_xml.starts_with("<iq") {
+                        let id = extract_attr(&stanza_xml, "id");
+                        let reply = format!("<iq type='result' id='{}'/>", id);
+                        let _ = socket.write_all(reply.as_bytes()).await;
+                    }
+                }
+            }
+        });
+
+        port
+    }
+
     #[tokio::test]
     async fn test_connect_disconnect() {
-        let config = Config::default();
+        let port = spawn_fake_server().await;
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
         let mut client = OpenFireClient::new(config).unwrap();
-        
+
         let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
-        
+
         // Test connection
         assert!(client.connect(creds).await.is_ok());
         assert!(client.is_connected());
-        
+
         // Test disconnection
         assert!(client.disconnect().await.is_ok());
         assert!(!client.is_connected());
     }
+
+    #[tokio::test]
+    async fn test_send_message_writes_an_xml_stanza_to_the_stream() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (received_tx, mut received_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                complete_live_handshake(&mut socket).await;
+                let mut buf = vec![0u8; 4096];
+                loop {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            let _ = received_tx.send(String::from_utf8_lossy(&buf[..n]).into_owned());
+                        }
+                    }
+                }
+            }
+        });
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        client.send_message("friend@localhost", "hi there").await.unwrap();
+
+        let received = tokio::time::timeout(Duration::from_secs(2), received_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(received.contains("<message"));
+        assert!(received.contains("<body>hi there</body>"));
+    }
+
+    #[test]
+    fn test_message_to_xml_includes_type_and_body() {
+        let message = Message::new_chat(
+            "user1@localhost".to_string(),
+            "user2@localhost".to_string(),
+            "Hello World!".to_string(),
+        );
+
+        let xml = message.to_xml();
+        assert!(xml.starts_with("<message type='chat'"));
+        assert!(xml.contains("<body>Hello World!</body>"));
+    }
+
+    #[test]
+    fn test_presence_to_xml_maps_status_to_show_element() {
+        let presence = Presence::new("user@localhost".to_string(), PresenceStatus::DoNotDisturb);
+        assert!(presence.to_xml().contains("<show>dnd</show>"));
+
+        let unavailable = Presence::new("user@localhost".to_string(), PresenceStatus::Unavailable);
+        assert!(unavailable.to_xml().contains("type='unavailable'"));
+    }
+
+    #[tokio::test]
+    async fn test_join_room_replays_archived_history() {
+        let port = spawn_fake_server().await;
+
+        let mut archive_path = std::env::temp_dir();
+        archive_path.push(format!("openfire_join_room_history_{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&archive_path);
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        config.mam_archive_path = Some(archive_path.clone());
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let archive = client.archive.clone().unwrap();
+        let mut past_message = Message::new_group_chat(
+            "room@conference.localhost/alice".to_string(),
+            "room@conference.localhost".to_string(),
+            "earlier message".to_string(),
+        );
+        past_message.timestamp = 1;
+        archive.store("room@conference.localhost", &past_message).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        client.event_tx = Some(tx);
+
+        client.join_room("room@conference.localhost", "bob", None).await.unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.unwrap().unwrap();
+        match event {
+            XmppEvent::MessageReceived(message) => assert_eq!(message.body, "earlier message"),
+            other => panic!("expected MessageReceived, got {:?}", other),
+        }
+
+        let _ = std::fs::remove_file(&archive_path);
+    }
+
+    /// A bare-bones HTTP/1.1 server that accepts one PUT and replies 200 OK,
+    /// standing in for the HTTP-upload component's PUT endpoint
+    async fn spawn_fake_put_server() -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                    .await;
+            }
+        });
+
+        port
+    }
+
+    /// Pull the `id='...'` attribute out of a raw stanza, as the fake XMPP
+    /// server below needs to in order to correlate its canned IQ replies
+    fn extract_attr<'a>(xml: &'a str, attr: &str) -> &'a str {
+        let needle = format!("{}='", attr);
+        let start = xml.find(&needle).unwrap() + needle.len();
+        let end = xml[start..].find('\'').unwrap();
+        &xml[start..start + end]
+    }
+
+    #[tokio::test]
+    async fn test_send_file_requests_slot_uploads_bytes_and_sends_oob_message() {
+        let put_port = spawn_fake_put_server().await;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let xmpp_port = listener.local_addr().unwrap().port();
+        let (sent_tx, mut sent_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                complete_live_handshake(&mut socket).await;
+                let mut buffer = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let n = match socket.read(&mut chunk).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n,
+                    };
+                    buffer.extend_from_slice(&chunk[..n]);
+
+                    let text = String::from_utf8_lossy(&buffer).into_owned();
+                    let Some((stanza_xml, consumed)) = stanza::next_complete_stanza(&text) else {
+                        continue;
+                    };
+                    buffer.drain(..consumed.min(buffer.len()));
+
+                    if !stanza_xml.starts_with("<iq") {
+                        let _ = sent_tx.send(stanza_xml);
+                        continue;
+                    }
+
+                    let id = extract_attr(&stanza_xml, "id");
+                    let reply = if stanza_xml.contains("disco#items") {
+                        format!(
+                            "<iq type='result' id='{}'><query xmlns='http://jabber.org/protocol/disco#items'>\
+                             <item jid='upload.localhost'/></query></iq>",
+                            id
+                        )
+                    } else if stanza_xml.contains("disco#info") {
+                        format!(
+                            "<iq type='result' id='{}'><query xmlns='http://jabber.org/protocol/disco#info'>\
+                             <feature var='urn:xmpp:http:upload:0'/>\
+                             <x xmlns='jabber:x:data' type='result'>\
+                             <field var='max-file-size'><value>1000000</value></field></x></query></iq>",
+                            id
+                        )
+                    } else {
+                        format!(
+                            "<iq type='result' id='{}'><slot xmlns='urn:xmpp:http:upload:0'>\
+                             <put url='http://127.0.0.1:{}/upload'/>\
+                             <get url='http://127.0.0.1:{}/files/report.txt'/></slot></iq>",
+                            id, put_port, put_port
+                        )
+                    };
+
+                    let _ = socket.write_all(reply.as_bytes()).await;
+                }
+            }
+        });
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = xmpp_port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        let mut path = std::env::temp_dir();
+        path.push(format!("openfire_send_file_{}.txt", std::process::id()));
+        std::fs::write(&path, b"report contents").unwrap();
+
+        let result = client.send_file("friend@localhost", &path).await;
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+
+        let sent_message = tokio::time::timeout(Duration::from_secs(2), sent_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(sent_message.contains(&format!("http://127.0.0.1:{}/files/report.txt", put_port)));
+        assert!(sent_message.contains("<x xmlns='jabber:x:oob'>"));
+    }
+
+    #[test]
+    fn test_apply_subscription_transition_matches_rfc6121_states() {
+        assert_eq!(apply_subscription_transition("none", "subscribed"), "to");
+        assert_eq!(apply_subscription_transition("from", "subscribed"), "both");
+        assert_eq!(apply_subscription_transition("both", "unsubscribed"), "from");
+        assert_eq!(apply_subscription_transition("to", "unsubscribed"), "none");
+        assert_eq!(apply_subscription_transition("both", "unsubscribe"), "to");
+        assert_eq!(apply_subscription_transition("from", "unsubscribe"), "none");
+    }
+
+    #[tokio::test]
+    async fn test_request_subscription_writes_a_subscribe_presence_stanza() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (received_tx, mut received_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                complete_live_handshake(&mut socket).await;
+                let mut buf = vec![0u8; 4096];
+                loop {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            let _ = received_tx.send(String::from_utf8_lossy(&buf[..n]).into_owned());
+                        }
+                    }
+                }
+            }
+        });
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        client.request_subscription("alice@localhost").await.unwrap();
+
+        let received = tokio::time::timeout(Duration::from_secs(2), received_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(received.contains("<presence type='subscribe' to='alice@localhost'/>"));
+    }
+
+    #[tokio::test]
+    async fn test_approve_subscription_transitions_contact_state() {
+        let port = spawn_fake_server().await;
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        client
+            .add_contact("alice@localhost", None, Vec::new())
+            .await
+            .unwrap();
+        client.approve_subscription("alice@localhost").await.unwrap();
+
+        let contacts = client.get_contacts().await;
+        let alice = contacts.iter().find(|c| c.jid == "alice@localhost").unwrap();
+        assert_eq!(alice.subscription, "from");
+
+        client.unsubscribe("alice@localhost").await.unwrap();
+        let contacts = client.get_contacts().await;
+        let alice = contacts.iter().find(|c| c.jid == "alice@localhost").unwrap();
+        assert_eq!(alice.subscription, "none");
+    }
+
+    /// A fake XMPP server that answers every disco#info IQ with a server
+    /// identity/feature set, unless the query targets `room@conference.localhost`,
+    /// in which case it answers `item-not-found`; and every disco#items IQ
+    /// with a single `upload.localhost` item
+    async fn spawn_fake_disco_server() -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                complete_live_handshake(&mut socket).await;
+                let mut buffer = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let n = match socket.read(&mut chunk).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n,
+                    };
+                    buffer.extend_from_slice(&chunk[..n]);
+
+                    let text = String::from_utf8_lossy(&buffer).into_owned();
+                    let Some((stanza_xml, consumed)) = stanza::next_complete_stanza(&text) else {
+                        continue;
+                    };
+                    buffer.drain(..consumed.min(buffer.len()));
+
+                    if !stanza_xml.starts_with("<iq") {
+                        continue;
+                    }
+
+                    let id = extract_attr(&stanza_xml, "id");
+                    let reply = if stanza_xml.contains("disco#items") {
+                        format!(
+                            "<iq type='result' id='{}'><query xmlns='http://jabber.org/protocol/disco#items'>\
+                             <item jid='upload.localhost' name='HTTP Upload'/></query></iq>",
+                            id
+                        )
+                    } else if stanza_xml.contains("room@conference.localhost") {
+                        format!(
+                            "<iq type='error' id='{}'><error type='cancel'>\
+                             <item-not-found xmlns='urn:ietf:params:xml:ns:xmpp-stanzas'/></error></iq>",
+                            id
+                        )
+                    } else {
+                        format!(
+                            "<iq type='result' id='{}'><query xmlns='http://jabber.org/protocol/disco#info'>\
+                             <identity category='server' type='im' name='OpenFire'/>\
+                             <feature var='http://jabber.org/protocol/muc'/></query></iq>",
+                            id
+                        )
+                    };
+
+                    let _ = socket.write_all(reply.as_bytes()).await;
+                }
+            }
+        });
+
+        port
+    }
+
+    #[tokio::test]
+    async fn test_get_server_info_parses_and_caches_disco_info() {
+        let port = spawn_fake_disco_server().await;
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        let info = client.get_server_info(&client.config.domain.clone()).await.unwrap();
+        assert!(info.supports("http://jabber.org/protocol/muc"));
+        assert_eq!(info.identities[0].name, Some("OpenFire".to_string()));
+
+        let cached = client.cached_server_info(&client.config.domain.clone()).await;
+        assert!(cached.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_disco_items_returns_advertised_components() {
+        let port = spawn_fake_disco_server().await;
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        let items = client.disco_items(&client.config.domain.clone()).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].jid, "upload.localhost");
+    }
+
+    #[tokio::test]
+    async fn test_room_info_returns_room_not_found_for_a_nonexistent_room() {
+        let port = spawn_fake_disco_server().await;
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        let result = client.room_info("room@conference.localhost").await;
+        assert!(matches!(result, Err(OpenFireError::RoomNotFound { jid }) if jid == "room@conference.localhost"));
+    }
+
+    /// A fake XMPP server that captures every IQ it receives (so a test can
+    /// assert on an outgoing bookmark publish) and answers every PubSub
+    /// `urn:xmpp:bookmarks:1` items request with one autojoin bookmark
+    async fn spawn_fake_bookmarks_server() -> (u16, mpsc::UnboundedReceiver<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sent_tx, sent_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            if let Ok((mut socket, _)) = listener.accept().await {
+                complete_live_handshake(&mut socket).await;
+                let mut buffer = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let n = match socket.read(&mut chunk).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n,
+                    };
+                    buffer.extend_from_slice(&chunk[..n]);
+
+                    let text = String::from_utf8_lossy(&buffer).into_owned();
+                    let Some((stanza_xml, consumed)) = stanza::next_complete_stanza(&text) else {
+                        continue;
+                    };
+                    buffer.drain(..consumed.min(buffer.len()));
+
+                    if !stanza_xml.starts_with("<iq") {
+                        continue;
+                    }
+
+                    let id = extract_attr(&stanza_xml, "id");
+                    let _ = sent_tx.send(stanza_xml.clone());
+
+                    if stanza_xml.contains("<publish ") {
+                        let reply = format!("<iq type='result' id='{}'/>", id);
+                        let _ = socket.write_all(reply.as_bytes()).await;
+                        continue;
+                    }
+
+                    if stanza_xml.contains("<items node='urn:xmpp:bookmarks:1'/>") {
+                        let reply = format!(
+                            "<iq type='result' id='{}'><pubsub xmlns='http://jabber.org/protocol/pubsub'>\
+                             <items node='urn:xmpp:bookmarks:1'>\
+                             <item id='lounge@conference.localhost'>\
+                             <conference xmlns='urn:xmpp:bookmarks:1' name='Lounge' autojoin='true'>\
+                             <nick>bob</nick><subject>Old topic</subject></conference></item>\
+                             </items></pubsub></iq>",
+                            id
+                        );
+                        let _ = socket.write_all(reply.as_bytes()).await;
+                    }
+                }
+            }
+        });
+
+        (port, sent_rx)
+    }
+
+    #[tokio::test]
+    async fn test_join_room_with_bookmark_publishes_a_conference_item() {
+        let (port, mut sent_rx) = spawn_fake_bookmarks_server().await;
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        client
+            .join_room("lounge@conference.localhost", "bob", Some(true))
+            .await
+            .unwrap();
+
+        let published = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                let stanza = sent_rx.recv().await.unwrap();
+                if stanza.contains("<publish ") {
+                    return stanza;
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(published.contains("<item id='lounge@conference.localhost'>"));
+        assert!(published.contains("autojoin='true'"));
+        assert!(published.contains("<nick>bob</nick>"));
+    }
+
+    #[tokio::test]
+    async fn test_load_bookmarks_returns_chat_rooms_with_persisted_subject() {
+        let (port, _sent_rx) = spawn_fake_bookmarks_server().await;
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        let rooms = client.load_bookmarks().await.unwrap();
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0].jid, "lounge@conference.localhost");
+        assert_eq!(rooms[0].subject, Some("Old topic".to_string()));
+        assert!(!rooms[0].joined);
+    }
+
+    #[tokio::test]
+    async fn test_autojoin_bookmarked_rooms_rejoins_and_restores_subject() {
+        let (port, _sent_rx) = spawn_fake_bookmarks_server().await;
+
+        let mut config = Config::default();
+        config.server = "127.0.0.1".to_string();
+        config.port = port;
+        config.auth_timeout = 1;
+        let mut client = OpenFireClient::new(config).unwrap();
+
+        let creds = Credentials::new("testuser".to_string(), "testpass".to_string());
+        client.connect(creds).await.unwrap();
+
+        let joined = client.autojoin_bookmarked_rooms().await.unwrap();
+        assert_eq!(joined, vec!["lounge@conference.localhost".to_string()]);
+
+        let rooms = client.get_chat_rooms().await;
+        let lounge = rooms.iter().find(|r| r.jid == "lounge@conference.localhost").unwrap();
+        assert!(lounge.joined);
+        assert_eq!(lounge.subject, Some("Old topic".to_string()));
+    }
 }
\ No newline at end of file
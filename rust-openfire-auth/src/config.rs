@@ -1,8 +1,11 @@
 //! Configuration management for OpenFire connections
 
 use serde::{Deserialize, Serialize};
+use crate::auth::SaslMechanism;
+use crate::credential_cache::Argon2Cost;
 use crate::error::{OpenFireError, Result};
-use std::path::Path;
+use crate::session::ReconnectPolicy;
+use std::path::{Path, PathBuf};
 
 /// OpenFire connection configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +36,55 @@ pub struct Config {
     
     /// Priority for presence (0-127)
     pub priority: i8,
+
+    /// SASL mechanisms to try during negotiation, strongest preference first
+    #[serde(default = "default_preferred_mechanisms")]
+    pub preferred_mechanisms: Vec<SaslMechanism>,
+
+    /// Path to the local Argon2id credential cache, if offline
+    /// re-authentication should be supported
+    #[serde(default)]
+    pub credential_cache_path: Option<PathBuf>,
+
+    /// Argon2id cost parameters used when writing to the credential cache
+    #[serde(default)]
+    pub credential_cache_cost: Argon2Cost,
+
+    /// Shared secret used to validate HMAC authentication tokens, if token
+    /// based authentication is enabled
+    #[serde(default)]
+    pub token_auth_secret: Option<String>,
+
+    /// Path to the trust-on-first-use store of approved server `host:port`
+    /// addresses, used by `AuthManager`'s host verification callback. This is
+    /// address-based trust, not a TLS certificate fingerprint store -- this
+    /// client has no TLS transport yet (see `AuthManager::verify_host_trust`)
+    #[serde(default)]
+    pub trust_store_path: Option<PathBuf>,
+
+    /// JIDs permitted to authenticate through this deployment, checked before
+    /// any network round-trip. Supports exact JIDs (`user@domain`) and
+    /// `*@domain` wildcards. Empty means no restriction beyond the server's.
+    #[serde(default)]
+    pub allowed_jids: Vec<String>,
+
+    /// Backoff policy used by `AuthManager::resume` when reconnecting a
+    /// dropped stream-management session
+    #[serde(default)]
+    pub reconnect_policy: ReconnectPolicy,
+
+    /// Path to the SQLite message archive (XEP-0313), if messages should be
+    /// persisted and replayed on room join
+    #[serde(default)]
+    pub mam_archive_path: Option<PathBuf>,
+}
+
+fn default_preferred_mechanisms() -> Vec<SaslMechanism> {
+    vec![
+        SaslMechanism::ScramSha256,
+        SaslMechanism::ScramSha1,
+        SaslMechanism::Plain,
+    ]
 }
 
 impl Default for Config {
@@ -47,6 +99,14 @@ impl Default for Config {
             auth_timeout: 10,
             resource: "SparkRust".to_string(),
             priority: 1,
+            preferred_mechanisms: default_preferred_mechanisms(),
+            credential_cache_path: None,
+            credential_cache_cost: Argon2Cost::default(),
+            token_auth_secret: None,
+            trust_store_path: None,
+            allowed_jids: Vec::new(),
+            reconnect_policy: ReconnectPolicy::default(),
+            mam_archive_path: None,
         }
     }
 }
@@ -0,0 +1,169 @@
+//! Local Argon2id credential cache used for offline re-authentication when
+//! the OpenFire server is briefly unreachable.
+
+use crate::auth::Credentials;
+use crate::error::{OpenFireError, Result};
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Cost parameters for the Argon2id hash backing the credential cache
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Argon2Cost {
+    pub memory_kib: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Cost {
+    fn default() -> Self {
+        // OWASP-recommended baseline for Argon2id
+        Self {
+            memory_kib: 19456,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// A single cached credential entry persisted to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedCredential {
+    argon2_phc_string: String,
+    full_jid: String,
+}
+
+/// On-disk store of Argon2id-hashed credentials, keyed by username
+pub struct CredentialCache {
+    path: PathBuf,
+    cost: Argon2Cost,
+}
+
+impl CredentialCache {
+    /// Create a cache backed by the JSON file at `path`
+    pub fn new<P: AsRef<Path>>(path: P, cost: Argon2Cost) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            cost,
+        }
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>> {
+        let params = Params::new(self.cost.memory_kib, self.cost.time_cost, self.cost.parallelism, None)
+            .map_err(|e| OpenFireError::ConfigError {
+                message: format!("Invalid Argon2 cost parameters: {}", e),
+            })?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    /// Hash `password` and persist it for `username`/`full_jid`. The password
+    /// buffer is zeroed once hashing is complete; only the PHC string is ever
+    /// written to disk.
+    pub fn store(&self, username: &str, mut password: String, full_jid: &str) -> Result<()> {
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2 = self.argon2()?;
+        let phc = argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| OpenFireError::Unknown {
+                message: format!("Failed to hash credentials for the offline cache: {}", e),
+            })?
+            .to_string();
+
+        // Safety: we only ever write zero bytes, so the buffer stays valid UTF-8.
+        unsafe {
+            for byte in password.as_bytes_mut() {
+                *byte = 0;
+            }
+        }
+        password.clear();
+
+        let mut entries = self.load_entries()?;
+        entries.insert(
+            username.to_string(),
+            CachedCredential {
+                argon2_phc_string: phc,
+                full_jid: full_jid.to_string(),
+            },
+        );
+        self.save_entries(&entries)
+    }
+
+    /// Verify `credentials` against the cached hash for its username, if any,
+    /// returning the cached `full_jid` on a match.
+    pub fn verify(&self, credentials: &Credentials) -> Result<Option<String>> {
+        let entries = self.load_entries()?;
+        let Some(entry) = entries.get(&credentials.username) else {
+            return Ok(None);
+        };
+
+        if credentials.verify_against_hash(&entry.argon2_phc_string)? {
+            Ok(Some(entry.full_jid.clone()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn load_entries(&self) -> Result<HashMap<String, CachedCredential>> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let content = std::fs::read_to_string(&self.path).map_err(|e| OpenFireError::ConfigError {
+            message: format!("Failed to read credential cache: {}", e),
+        })?;
+
+        serde_json::from_str(&content).map_err(|e| OpenFireError::SerializationError {
+            message: format!("Failed to parse credential cache: {}", e),
+        })
+    }
+
+    fn save_entries(&self, entries: &HashMap<String, CachedCredential>) -> Result<()> {
+        let content = serde_json::to_string_pretty(entries).map_err(|e| OpenFireError::SerializationError {
+            message: format!("Failed to serialize credential cache: {}", e),
+        })?;
+
+        std::fs::write(&self.path, content).map_err(|e| OpenFireError::ConfigError {
+            message: format!("Failed to write credential cache: {}", e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_then_verify_round_trips() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("openfire_cred_cache_test_{:?}.json", std::thread::current().id()));
+        let _ = std::fs::remove_file(&path);
+
+        let cache = CredentialCache::new(&path, Argon2Cost::default());
+        let creds = Credentials::new("offlineuser".to_string(), "correcthorse".to_string());
+
+        cache
+            .store(&creds.username, creds.password.clone(), "offlineuser@localhost")
+            .unwrap();
+
+        let matched = cache.verify(&creds).unwrap();
+        assert_eq!(matched, Some("offlineuser@localhost".to_string()));
+
+        let wrong_creds = Credentials::new("offlineuser".to_string(), "wrongpass".to_string());
+        assert_eq!(cache.verify(&wrong_creds).unwrap(), None);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_verify_unknown_username_returns_none() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("openfire_cred_cache_missing_{:?}.json", std::thread::current().id()));
+        let _ = std::fs::remove_file(&path);
+
+        let cache = CredentialCache::new(&path, Argon2Cost::default());
+        let creds = Credentials::new("ghost".to_string(), "whatever".to_string());
+        assert_eq!(cache.verify(&creds).unwrap(), None);
+    }
+}
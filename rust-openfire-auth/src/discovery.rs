@@ -0,0 +1,256 @@
+//! Service discovery (XEP-0030) IQ payloads and response parsing. Driving
+//! the IQ round-trip is `communication::OpenFireClient`'s job.
+
+use crate::error::{OpenFireError, Result};
+use crate::stanza::find_attr;
+use quick_xml::events::Event;
+use quick_xml::reader::NsReader;
+
+/// One `<identity category='...' type='...' name='...'/>` advertised by a
+/// disco#info response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub category: String,
+    pub kind: String,
+    pub name: Option<String>,
+}
+
+/// The identities and features a JID advertises via disco#info
+#[derive(Debug, Clone, Default)]
+pub struct ServerInfo {
+    pub identities: Vec<Identity>,
+    pub features: Vec<String>,
+}
+
+impl ServerInfo {
+    /// Whether this JID advertises `feature` (e.g. `http://jabber.org/protocol/muc`)
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+/// One `<item jid='...' name='...' node='...'/>` advertised by a disco#items response
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoItem {
+    pub jid: String,
+    pub name: Option<String>,
+    pub node: Option<String>,
+}
+
+/// A MUC room's advertised features and current occupant count
+#[derive(Debug, Clone)]
+pub struct RoomInfo {
+    pub jid: String,
+    pub features: Vec<String>,
+    pub occupant_count: Option<u32>,
+}
+
+/// Build the `<query xmlns='http://jabber.org/protocol/disco#info'/>` IQ payload
+pub(crate) fn disco_info_payload() -> &'static str {
+    "<query xmlns='http://jabber.org/protocol/disco#info'/>"
+}
+
+/// Build the `<query xmlns='http://jabber.org/protocol/disco#items'/>` IQ payload
+pub(crate) fn disco_items_payload() -> &'static str {
+    "<query xmlns='http://jabber.org/protocol/disco#items'/>"
+}
+
+/// Parse a disco#info result into its advertised identities and features
+pub(crate) fn parse_disco_info(xml: &str) -> Result<ServerInfo> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut info = ServerInfo::default();
+
+    loop {
+        match reader.read_event().map_err(|e| OpenFireError::XmppProtocolError {
+            message: format!("Failed to parse disco#info response: {}", e),
+        })? {
+            Event::Start(e) | Event::Empty(e) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match local.as_str() {
+                    "identity" => info.identities.push(Identity {
+                        category: find_attr(&e, "category").unwrap_or_default(),
+                        kind: find_attr(&e, "type").unwrap_or_default(),
+                        name: find_attr(&e, "name"),
+                    }),
+                    "feature" => {
+                        if let Some(var) = find_attr(&e, "var") {
+                            info.features.push(var);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(info)
+}
+
+/// Parse a disco#items result into its child items
+pub(crate) fn parse_disco_items(xml: &str) -> Vec<DiscoItem> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut items = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name().as_ref() == b"item" => {
+                if let Some(jid) = find_attr(&e, "jid") {
+                    items.push(DiscoItem {
+                        jid,
+                        name: find_attr(&e, "name"),
+                        node: find_attr(&e, "node"),
+                    });
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    items
+}
+
+/// Read the text value of a XEP-0068 data form field named `var`, e.g.
+/// `muc#roominfo_occupants` in a MUC room's disco#info result
+pub(crate) fn parse_data_form_field(xml: &str, var: &str) -> Option<String> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut in_field = false;
+    let mut expect_value = false;
+    let mut value = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match local.as_str() {
+                    "field" => in_field = find_attr(&e, "var").as_deref() == Some(var),
+                    "value" if in_field => expect_value = true,
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(t)) if expect_value => {
+                value = t.unescape().ok().map(|v| v.into_owned());
+                expect_value = false;
+            }
+            Ok(Event::End(e)) if e.local_name().as_ref() == b"field" => in_field = false,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    value
+}
+
+/// Parse a MUC room's disco#info result into its features and occupant count
+pub(crate) fn parse_room_info(room_jid: &str, xml: &str) -> Result<RoomInfo> {
+    let info = parse_disco_info(xml)?;
+    let occupant_count = parse_data_form_field(xml, "muc#roominfo_occupants").and_then(|v| v.parse().ok());
+
+    Ok(RoomInfo {
+        jid: room_jid.to_string(),
+        features: info.features,
+        occupant_count,
+    })
+}
+
+/// If a raw `<iq>` response is a `type='error'` stanza, return its RFC 6120
+/// `<error>` condition name (e.g. `item-not-found`, `not-authorized`); `None`
+/// for anything else, including a well-formed `type='result'` response
+pub(crate) fn iq_error_condition(xml: &str) -> Option<String> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut is_error = false;
+    let mut in_error_element = false;
+    let mut condition = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match local.as_str() {
+                    "iq" if find_attr(&e, "type").as_deref() == Some("error") => is_error = true,
+                    "error" => in_error_element = true,
+                    "text" => {}
+                    _ if in_error_element && condition.is_none() => condition = Some(local),
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    if is_error {
+        condition
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_disco_info_extracts_identities_and_features() {
+        let xml = "<query xmlns='http://jabber.org/protocol/disco#info'>\
+            <identity category='server' type='im' name='OpenFire'/>\
+            <feature var='http://jabber.org/protocol/muc'/>\
+            <feature var='urn:xmpp:http:upload:0'/>\
+            </query>";
+
+        let info = parse_disco_info(xml).unwrap();
+        assert_eq!(info.identities.len(), 1);
+        assert_eq!(info.identities[0].category, "server");
+        assert_eq!(info.identities[0].kind, "im");
+        assert_eq!(info.identities[0].name, Some("OpenFire".to_string()));
+        assert!(info.supports("http://jabber.org/protocol/muc"));
+        assert!(!info.supports("urn:xmpp:mix:core:1"));
+    }
+
+    #[test]
+    fn test_parse_disco_items_extracts_items() {
+        let xml = "<query xmlns='http://jabber.org/protocol/disco#items'>\
+            <item jid='conference.localhost' name='Chatrooms'/>\
+            <item jid='upload.localhost'/>\
+            </query>";
+
+        let items = parse_disco_items(xml);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].jid, "conference.localhost");
+        assert_eq!(items[0].name, Some("Chatrooms".to_string()));
+        assert_eq!(items[1].jid, "upload.localhost");
+        assert_eq!(items[1].node, None);
+    }
+
+    #[test]
+    fn test_parse_room_info_extracts_features_and_occupant_count() {
+        let xml = "<query xmlns='http://jabber.org/protocol/disco#info'>\
+            <feature var='muc_persistent'/>\
+            <x xmlns='jabber:x:data' type='result'>\
+            <field var='muc#roominfo_occupants'><value>7</value></field>\
+            </x></query>";
+
+        let room = parse_room_info("room@conference.localhost", xml).unwrap();
+        assert_eq!(room.jid, "room@conference.localhost");
+        assert_eq!(room.features, vec!["muc_persistent".to_string()]);
+        assert_eq!(room.occupant_count, Some(7));
+    }
+
+    #[test]
+    fn test_iq_error_condition_extracts_the_condition_name() {
+        let xml = "<iq type='error' id='1'><error type='cancel'>\
+            <item-not-found xmlns='urn:ietf:params:xml:ns:xmpp-stanzas'/></error></iq>";
+        assert_eq!(iq_error_condition(xml), Some("item-not-found".to_string()));
+
+        let result_xml = "<iq type='result' id='1'><query/></iq>";
+        assert_eq!(iq_error_condition(result_xml), None);
+    }
+}
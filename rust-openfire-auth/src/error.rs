@@ -23,6 +23,9 @@ pub enum OpenFireError {
     #[error("TLS/SSL error: {message}")]
     TlsError { message: String },
 
+    #[error("Host trust rejected for {host}")]
+    HostTrustRejected { host: String },
+
     #[error("Timeout error: operation timed out after {seconds} seconds")]
     TimeoutError { seconds: u64 },
 
@@ -38,6 +41,18 @@ pub enum OpenFireError {
     #[error("Server not reachable: {server}")]
     ServerUnreachable { server: String },
 
+    #[error("JID not permitted by allowlist: {jid}")]
+    JidNotAllowed { jid: String },
+
+    #[error("Room not found: {jid}")]
+    RoomNotFound { jid: String },
+
+    #[error("SASL mechanism negotiation failed: {message}")]
+    SaslMechanismNegotiationFailed { message: String },
+
+    #[error("SASL authentication rejected: {message}")]
+    SaslAuthenticationRejected { message: String },
+
     #[error("Unknown error: {message}")]
     Unknown { message: String },
 }
@@ -189,17 +189,11 @@ pub extern "system" fn Java_org_jivesoftware_spark_openfire_OpenFireAuthNative_c
         Credentials::with_domain(username_str, password_str, domain_str.clone())
     };
 
-    let result = RUNTIME.block_on(client.connect(credentials.clone()));
-    
+    let result = RUNTIME.block_on(client.connect(credentials));
+
     match result {
-        Ok(_) => {
-            let success_result = AuthResult::success(
-                format!("{}@{}", credentials.username, domain_str),
-                Some("session_id".to_string()),
-                100,
-            );
-            
-            match auth_result_to_json(&success_result) {
+        Ok(auth_result) => {
+            match auth_result_to_json(&auth_result) {
                 Ok(json) => {
                     match rust_string_to_jstring(&mut env, &json) {
                         Ok(jstr) => jstr,
@@ -446,7 +440,7 @@ pub extern "system" fn Java_org_jivesoftware_spark_openfire_OpenFireAuthNative_j
         }
     };
 
-    match RUNTIME.block_on(client.join_room(&room_jid_str, &nickname_str)) {
+    match RUNTIME.block_on(client.join_room(&room_jid_str, &nickname_str, None)) {
         Ok(_) => {
             info!("Joined room {} as {}", room_jid_str, nickname_str);
             JNI_TRUE
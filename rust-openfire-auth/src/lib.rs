@@ -3,11 +3,22 @@
 //! This library provides authentication and communication functionality
 //! for connecting to OpenFire XMPP servers from Rust.
 
+pub mod archive;
 pub mod auth;
+pub mod bookmarks;
 pub mod communication;
 pub mod config;
+pub mod credential_cache;
+pub mod discovery;
 pub mod error;
 pub mod jni_interface;
+pub mod mam;
+pub mod scram;
+pub mod session;
+pub mod sso;
+pub mod stanza;
+pub mod token_auth;
+pub mod upload;
 
 pub use auth::AuthManager;
 pub use communication::OpenFireClient;
@@ -0,0 +1,251 @@
+//! Message Archive Management (XEP-0313) IQ payloads and wire-format
+//! parsing. Driving the IQ round-trip -- and archiving/forwarding whatever
+//! `<result/>` messages stream back before the `<fin/>` arrives -- is
+//! `communication::OpenFireClient`'s job.
+
+use crate::communication::{current_timestamp, Message, MessageType};
+use crate::error::{OpenFireError, Result};
+use crate::stanza::{escape_xml, find_attr};
+use quick_xml::events::Event;
+use quick_xml::reader::NsReader;
+use std::collections::HashMap;
+
+pub(crate) const MAM_NS: &str = "urn:xmpp:mam:2";
+
+/// Outcome of a completed MAM query, parsed from the `<fin/>` element in the
+/// query IQ's result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MamFin {
+    /// Whether the server reported this as the full result set, i.e. there's
+    /// no earlier page left to request
+    pub complete: bool,
+}
+
+/// Build the `<query xmlns='urn:xmpp:mam:2' queryid='..'>` IQ payload,
+/// optionally filtered to messages `with` a specific JID, capped at `limit`
+/// results via RSM (XEP-0059) `<max/>`
+pub(crate) fn query_payload(query_id: &str, with: Option<&str>, limit: u32) -> String {
+    let mut form = format!(
+        "<x xmlns='jabber:x:data' type='submit'>\
+         <field var='FORM_TYPE' type='hidden'><value>{}</value></field>",
+        MAM_NS
+    );
+    if let Some(with) = with {
+        form.push_str(&format!(
+            "<field var='with'><value>{}</value></field>",
+            escape_xml(with)
+        ));
+    }
+    form.push_str("</x>");
+
+    format!(
+        "<query xmlns='{}' queryid='{}'>{}<set xmlns='http://jabber.org/protocol/rsm'><max>{}</max></set></query>",
+        MAM_NS,
+        escape_xml(query_id),
+        form,
+        limit
+    )
+}
+
+/// Parse the `<fin xmlns='urn:xmpp:mam:2'>` element in a MAM query's IQ result
+pub(crate) fn parse_fin(xml: &str) -> MamFin {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut complete = false;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name().as_ref() == b"fin" => {
+                complete = find_attr(&e, "complete").as_deref() == Some("true");
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    MamFin { complete }
+}
+
+/// Parse a `<message><result queryid='..' id='..'><forwarded><delay
+/// stamp='..'/><message .../></forwarded></result></message>` envelope into
+/// the query id it answers and the archived message it carries
+pub(crate) fn parse_result_message(xml: &str) -> Result<Option<(String, Message)>> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut query_id = None;
+    let mut timestamp = None;
+    let mut in_forwarded_message = false;
+    let mut attrs: HashMap<String, String> = HashMap::new();
+    let mut current_child: Option<String> = None;
+    let mut subject = None;
+    let mut body = String::new();
+    let mut thread = None;
+
+    loop {
+        let event = reader.read_event().map_err(|e| OpenFireError::XmppProtocolError {
+            message: format!("Failed to parse MAM result message: {}", e),
+        })?;
+
+        match event {
+            Event::Eof => break,
+            Event::Start(e) | Event::Empty(e) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match local.as_str() {
+                    "result" => query_id = find_attr(&e, "queryid"),
+                    "forwarded" => in_forwarded_message = true,
+                    "delay" => timestamp = find_attr(&e, "stamp").and_then(|s| parse_xep0082_timestamp(&s)),
+                    "message" if in_forwarded_message => {
+                        for attr in e.attributes().flatten() {
+                            let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned();
+                            let value = attr.unescape_value().unwrap_or_default().into_owned();
+                            attrs.insert(key, value);
+                        }
+                    }
+                    _ => {}
+                }
+                current_child = Some(local);
+            }
+            Event::Text(t) => {
+                // Only collect text once inside the `<forwarded>` envelope --
+                // otherwise a sibling same-named element on the outer
+                // `<message>`/`<result>` would bleed into the archived
+                // message's fields.
+                if in_forwarded_message {
+                    let text = t.unescape().unwrap_or_default().into_owned();
+                    match current_child.as_deref() {
+                        Some("body") => body.push_str(&text),
+                        Some("subject") => subject = Some(text),
+                        Some("thread") => thread = Some(text),
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(_) => current_child = None,
+            _ => {}
+        }
+    }
+
+    let Some(query_id) = query_id else {
+        return Ok(None);
+    };
+
+    let message_type = match attrs.get("type").map(String::as_str) {
+        Some("groupchat") => MessageType::GroupChat,
+        Some("headline") => MessageType::Headline,
+        Some("error") => MessageType::Error,
+        Some("normal") => MessageType::Normal,
+        _ => MessageType::Chat,
+    };
+
+    Ok(Some((
+        query_id,
+        Message {
+            id: attrs.remove("id").unwrap_or_default(),
+            from: attrs.remove("from").unwrap_or_default(),
+            to: attrs.remove("to").unwrap_or_default(),
+            message_type,
+            subject,
+            body,
+            timestamp: timestamp.unwrap_or_else(current_timestamp),
+            thread,
+        },
+    )))
+}
+
+/// Parse a XEP-0082 UTC timestamp (`2023-01-01T12:00:00.000Z`, fractional
+/// seconds optional) into Unix seconds. Falls back to `current_timestamp()`
+/// at the call site rather than pulling in a date/time dependency for this
+/// one field.
+fn parse_xep0082_timestamp(stamp: &str) -> Option<u64> {
+    let stamp = stamp.strip_suffix('Z')?;
+    let (date, time) = stamp.split_once('T')?;
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+
+    let time = time.split('.').next()?;
+    let mut time_parts = time.splitn(3, ':');
+    let hour: i64 = time_parts.next()?.parse().ok()?;
+    let minute: i64 = time_parts.next()?.parse().ok()?;
+    let second: i64 = time_parts.next()?.parse().ok()?;
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second;
+    u64::try_from(seconds).ok()
+}
+
+/// Howard Hinnant's `days_from_civil`: days since the Unix epoch for a
+/// proleptic-Gregorian (year, month, day), valid for any year
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_payload_includes_with_filter_and_max() {
+        let payload = query_payload("q1", Some("juliet@example.com"), 20);
+        assert!(payload.contains("queryid='q1'"));
+        assert!(payload.contains("<value>juliet@example.com</value>"));
+        assert!(payload.contains("<max>20</max>"));
+    }
+
+    #[test]
+    fn test_query_payload_omits_with_filter_when_absent() {
+        let payload = query_payload("q1", None, 50);
+        assert!(!payload.contains("var='with'"));
+    }
+
+    #[test]
+    fn test_parse_fin_reports_complete() {
+        let xml = "<iq type='result' id='q1'><fin xmlns='urn:xmpp:mam:2' complete='true'>\
+            <set xmlns='http://jabber.org/protocol/rsm'><first>1</first><last>2</last></set>\
+            </fin></iq>";
+        assert!(parse_fin(xml).complete);
+    }
+
+    #[test]
+    fn test_parse_fin_reports_incomplete_by_default() {
+        let xml = "<iq type='result' id='q1'><fin xmlns='urn:xmpp:mam:2'/></iq>";
+        assert!(!parse_fin(xml).complete);
+    }
+
+    #[test]
+    fn test_parse_result_message_extracts_forwarded_message_and_delay() {
+        let xml = "<message from='juliet@example.com' to='romeo@example.com/laptop'>\
+            <result xmlns='urn:xmpp:mam:2' queryid='q1' id='28482-98726-73623'>\
+            <forwarded xmlns='urn:xmpp:forward:0'>\
+            <delay xmlns='urn:xmpp:delay' stamp='2010-07-10T23:08:25Z'/>\
+            <message xmlns='jabber:client' from='witch@shakespeare.lit' to='macbeth@shakespeare.lit' type='chat'>\
+            <body>Hail to thee</body></message>\
+            </forwarded></result></message>";
+
+        let (query_id, message) = parse_result_message(xml).unwrap().unwrap();
+        assert_eq!(query_id, "q1");
+        assert_eq!(message.from, "witch@shakespeare.lit");
+        assert_eq!(message.to, "macbeth@shakespeare.lit");
+        assert_eq!(message.body, "Hail to thee");
+        assert_eq!(message.message_type, MessageType::Chat);
+        assert_eq!(message.timestamp, 1278803305);
+    }
+
+    #[test]
+    fn test_parse_result_message_returns_none_without_queryid() {
+        let xml = "<message><result xmlns='urn:xmpp:mam:2' id='1'>\
+            <forwarded xmlns='urn:xmpp:forward:0'><message><body>hi</body></message></forwarded>\
+            </result></message>";
+        assert!(parse_result_message(xml).unwrap().is_none());
+    }
+}
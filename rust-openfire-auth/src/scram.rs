@@ -0,0 +1,318 @@
+//! SCRAM-SHA-256 (RFC 5802 / RFC 7677) client-side SASL mechanics
+//!
+//! This module only implements the cryptographic and message-parsing parts
+//! of the exchange; driving it over a live connection is the caller's job
+//! (see [`crate::auth::AuthManager`]).
+
+use crate::error::{OpenFireError, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2_hmac;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+type HmacSha1 = Hmac<Sha1>;
+
+/// Which hash function backs a SCRAM exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScramHash {
+    Sha256,
+    Sha1,
+}
+
+/// Generate a fresh client nonce for a single SCRAM exchange
+pub fn generate_client_nonce() -> String {
+    let bytes: [u8; 18] = rand::random();
+    STANDARD.encode(bytes)
+}
+
+/// The client-first SASL message, split into its GS2 header and bare body
+pub struct ClientFirst {
+    pub gs2_header: String,
+    pub bare: String,
+}
+
+/// Build the client-first message for the given username and nonce
+pub fn client_first_message(username: &str, nonce: &str) -> ClientFirst {
+    let bare = format!("n={},r={}", escape_username(username), nonce);
+    ClientFirst {
+        gs2_header: "n,,".to_string(),
+        bare,
+    }
+}
+
+fn escape_username(username: &str) -> String {
+    username.replace('=', "=3D").replace(',', "=2C")
+}
+
+/// Parsed server-first SASL message: `r=<nonce>,s=<salt>,i=<iterations>`
+pub struct ServerFirst {
+    pub nonce: String,
+    pub salt: Vec<u8>,
+    pub iterations: u32,
+}
+
+/// Parse and validate a server-first message against the client nonce we sent
+pub fn parse_server_first(message: &str, client_nonce: &str) -> Result<ServerFirst> {
+    let mut nonce = None;
+    let mut salt = None;
+    let mut iterations = None;
+
+    for field in message.split(',') {
+        let mut parts = field.splitn(2, '=');
+        let key = parts.next().unwrap_or_default();
+        let value = parts.next().unwrap_or_default();
+        match key {
+            "r" => nonce = Some(value.to_string()),
+            "s" => salt = Some(value.to_string()),
+            "i" => iterations = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    let nonce = nonce.ok_or_else(|| OpenFireError::XmppProtocolError {
+        message: "SCRAM server-first message missing nonce".to_string(),
+    })?;
+
+    if !nonce.starts_with(client_nonce) {
+        return Err(OpenFireError::AuthenticationFailed {
+            message: "SCRAM server nonce does not extend the client nonce".to_string(),
+        });
+    }
+
+    let salt = salt.ok_or_else(|| OpenFireError::XmppProtocolError {
+        message: "SCRAM server-first message missing salt".to_string(),
+    })?;
+    let salt = STANDARD
+        .decode(salt)
+        .map_err(|e| OpenFireError::XmppProtocolError {
+            message: format!("Invalid base64 salt in SCRAM server-first message: {}", e),
+        })?;
+
+    let iterations = iterations.ok_or_else(|| OpenFireError::XmppProtocolError {
+        message: "SCRAM server-first message missing iteration count".to_string(),
+    })?;
+    let iterations: u32 = iterations
+        .parse()
+        .map_err(|e| OpenFireError::XmppProtocolError {
+            message: format!("Invalid iteration count in SCRAM server-first message: {}", e),
+        })?;
+
+    Ok(ServerFirst {
+        nonce,
+        salt,
+        iterations,
+    })
+}
+
+/// The computed client-final message, plus the server signature we expect
+/// back so it can be verified once the server-final message arrives.
+pub struct ClientFinal {
+    pub message: String,
+    pub expected_server_signature: Vec<u8>,
+}
+
+/// Compute the client-final message and the expected server signature
+pub fn compute_client_final(
+    hash: ScramHash,
+    password: &str,
+    client_first_bare: &str,
+    server_first: &str,
+    server_first_parsed: &ServerFirst,
+) -> Result<ClientFinal> {
+    let salted_password = salted_password(hash, password, &server_first_parsed.salt, server_first_parsed.iterations);
+
+    let client_key = hmac(hash, &salted_password, b"Client Key");
+    let stored_key = digest(hash, &client_key);
+
+    let channel_binding = STANDARD.encode("n,,");
+    let client_final_without_proof = format!("c={},r={}", channel_binding, server_first_parsed.nonce);
+
+    let auth_message = format!(
+        "{},{},{}",
+        client_first_bare, server_first, client_final_without_proof
+    );
+
+    let client_signature = hmac(hash, &stored_key, auth_message.as_bytes());
+    let client_proof: Vec<u8> = client_key
+        .iter()
+        .zip(client_signature.iter())
+        .map(|(a, b)| a ^ b)
+        .collect();
+
+    let server_key = hmac(hash, &salted_password, b"Server Key");
+    let expected_server_signature = hmac(hash, &server_key, auth_message.as_bytes());
+
+    let message = format!(
+        "{},p={}",
+        client_final_without_proof,
+        STANDARD.encode(client_proof)
+    );
+
+    Ok(ClientFinal {
+        message,
+        expected_server_signature,
+    })
+}
+
+/// Verify a server-final message (`v=<signature>`) against the expected signature
+pub fn verify_server_signature(message: &str, expected: &[u8]) -> Result<()> {
+    let signature = message
+        .strip_prefix("v=")
+        .ok_or_else(|| OpenFireError::XmppProtocolError {
+            message: "SCRAM server-final message missing signature".to_string(),
+        })?;
+    let signature = STANDARD
+        .decode(signature)
+        .map_err(|e| OpenFireError::XmppProtocolError {
+            message: format!("Invalid base64 server signature: {}", e),
+        })?;
+
+    if !constant_time_eq(&signature, expected) {
+        return Err(OpenFireError::AuthenticationFailed {
+            message: "SCRAM server signature verification failed".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Compare two byte slices without leaking how many leading bytes matched,
+/// unlike `!=` -- the server signature compared here is secret-derived, the
+/// same reasoning `token_auth`'s `constant_time_eq` follows for HMAC tags.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+fn salted_password(hash: ScramHash, password: &str, salt: &[u8], iterations: u32) -> Vec<u8> {
+    match hash {
+        ScramHash::Sha256 => {
+            let mut output = [0u8; 32];
+            pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut output);
+            output.to_vec()
+        }
+        ScramHash::Sha1 => {
+            let mut output = [0u8; 20];
+            pbkdf2_hmac::<Sha1>(password.as_bytes(), salt, iterations, &mut output);
+            output.to_vec()
+        }
+    }
+}
+
+fn hmac(hash: ScramHash, key: &[u8], data: &[u8]) -> Vec<u8> {
+    match hash {
+        ScramHash::Sha256 => {
+            let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+        ScramHash::Sha1 => {
+            let mut mac = HmacSha1::new_from_slice(key).expect("HMAC accepts keys of any length");
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+    }
+}
+
+fn digest(hash: ScramHash, data: &[u8]) -> Vec<u8> {
+    match hash {
+        ScramHash::Sha256 => Sha256::digest(data).to_vec(),
+        ScramHash::Sha1 => Sha1::digest(data).to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_first_message_escapes_reserved_chars() {
+        let first = client_first_message("a=b,c", "abcd");
+        assert_eq!(first.gs2_header, "n,,");
+        assert_eq!(first.bare, "n=a=3Db=2Cc,r=abcd");
+    }
+
+    #[test]
+    fn test_parse_server_first_rejects_mismatched_nonce() {
+        let result = parse_server_first("r=other,s=c2FsdA==,i=4096", "abcd");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_server_first_rejects_bad_base64_salt() {
+        let result = parse_server_first("r=abcdserver,s=not-base64!!,i=4096", "abcd");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_full_exchange_round_trips() {
+        let client_nonce = "clientnonce";
+        let client_first = client_first_message("user", client_nonce);
+
+        let salt = b"pepper-salt".to_vec();
+        let iterations = 4096;
+        let server_nonce = format!("{}servernonce", client_nonce);
+        let server_first = format!(
+            "r={},s={},i={}",
+            server_nonce,
+            STANDARD.encode(&salt),
+            iterations
+        );
+
+        let parsed = parse_server_first(&server_first, client_nonce).unwrap();
+        let client_final = compute_client_final(
+            ScramHash::Sha256,
+            "hunter2",
+            &client_first.bare,
+            &server_first,
+            &parsed,
+        )
+        .unwrap();
+
+        let server_final = format!(
+            "v={}",
+            STANDARD.encode(&client_final.expected_server_signature)
+        );
+        assert!(verify_server_signature(&server_final, &client_final.expected_server_signature).is_ok());
+        assert!(client_final.message.starts_with("c="));
+    }
+
+    #[test]
+    fn test_full_exchange_round_trips_with_sha1() {
+        let client_nonce = "clientnonce";
+        let client_first = client_first_message("user", client_nonce);
+
+        let server_nonce = format!("{}servernonce", client_nonce);
+        let server_first = format!(
+            "r={},s={},i={}",
+            server_nonce,
+            STANDARD.encode(b"pepper-salt"),
+            4096
+        );
+
+        let parsed = parse_server_first(&server_first, client_nonce).unwrap();
+        let client_final = compute_client_final(
+            ScramHash::Sha1,
+            "hunter2",
+            &client_first.bare,
+            &server_first,
+            &parsed,
+        )
+        .unwrap();
+
+        let server_final = format!(
+            "v={}",
+            STANDARD.encode(&client_final.expected_server_signature)
+        );
+        assert!(verify_server_signature(&server_final, &client_final.expected_server_signature).is_ok());
+    }
+}
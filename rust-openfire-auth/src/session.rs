@@ -0,0 +1,55 @@
+//! Stream-management (XEP-0198) session resumption state and reconnect backoff
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A resumable XMPP stream-management session established after a successful bind
+#[derive(Debug, Clone)]
+pub struct ResumableSession {
+    pub resumption_id: String,
+    pub stanza_counter: u64,
+}
+
+/// Exponential-backoff retry policy used when reconnecting a dropped session
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReconnectPolicy {
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            max_attempts: 5,
+        }
+    }
+}
+
+/// The delay before reconnect attempt `attempt` (0-indexed), doubling each
+/// time and capped at `max_delay_ms`
+pub fn backoff_delay(policy: &ReconnectPolicy, attempt: u32) -> Duration {
+    let scaled = policy.base_delay_ms.saturating_mul(1u64 << attempt.min(32));
+    Duration::from_millis(scaled.min(policy.max_delay_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay_doubles_and_caps() {
+        let policy = ReconnectPolicy {
+            base_delay_ms: 100,
+            max_delay_ms: 1000,
+            max_attempts: 10,
+        };
+
+        assert_eq!(backoff_delay(&policy, 0), Duration::from_millis(100));
+        assert_eq!(backoff_delay(&policy, 1), Duration::from_millis(200));
+        assert_eq!(backoff_delay(&policy, 2), Duration::from_millis(400));
+        assert_eq!(backoff_delay(&policy, 10), Duration::from_millis(1000));
+    }
+}
@@ -0,0 +1,336 @@
+//! OAuth2/OIDC single sign-on login flow, completed via a short-lived local
+//! redirect listener and PKCE (RFC 7636).
+
+use crate::error::{OpenFireError, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+
+/// Configuration describing an OAuth2/OIDC provider fronting an OpenFire deployment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoProviderConfig {
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub client_id: String,
+    /// Loopback port the local redirect listener binds to; must match the
+    /// provider's registered redirect URI (`http://127.0.0.1:<port>/callback`)
+    pub redirect_port: u16,
+    pub scope: String,
+    /// The XMPP username this login is expected to resolve to
+    pub username_hint: Option<String>,
+}
+
+/// The authorization URL to open, plus the PKCE/state material needed to
+/// complete the flow once the redirect arrives
+pub struct SsoAuthorizationRequest {
+    pub authorization_url: String,
+    pub state: String,
+    code_verifier: String,
+}
+
+/// The result of exchanging an authorization code for an access token
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub token_type: String,
+}
+
+/// Build the authorization URL together with a random `state` and PKCE challenge
+pub fn build_authorization_request(provider: &SsoProviderConfig) -> SsoAuthorizationRequest {
+    let state = random_url_safe_token();
+    let code_verifier = random_url_safe_token();
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+    let redirect_uri = redirect_uri(provider.redirect_port);
+
+    let authorization_url = format!(
+        "{}?response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&code_challenge={}&code_challenge_method=S256",
+        provider.authorization_endpoint,
+        percent_encode(&provider.client_id),
+        percent_encode(&redirect_uri),
+        percent_encode(&provider.scope),
+        percent_encode(&state),
+        percent_encode(&code_challenge),
+    );
+
+    SsoAuthorizationRequest {
+        authorization_url,
+        state,
+        code_verifier,
+    }
+}
+
+/// Wait for the provider's redirect to arrive on the loopback listener,
+/// enforcing `wait` as a hard timeout and rejecting a `state` mismatch (CSRF)
+pub async fn await_redirect(
+    request: &SsoAuthorizationRequest,
+    port: u16,
+    wait: Duration,
+) -> Result<String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| OpenFireError::ConnectionError {
+            message: format!("Failed to bind SSO redirect listener on port {}: {}", port, e),
+        })?;
+
+    let (mut stream, _) = timeout(wait, listener.accept())
+        .await
+        .map_err(|_| OpenFireError::TimeoutError { seconds: wait.as_secs() })?
+        .map_err(|e| OpenFireError::ConnectionError {
+            message: format!("Failed to accept SSO redirect connection: {}", e),
+        })?;
+
+    let mut buffer = vec![0u8; 8192];
+    let bytes_read = stream
+        .read(&mut buffer)
+        .await
+        .map_err(|e| OpenFireError::ConnectionError {
+            message: format!("Failed to read SSO redirect request: {}", e),
+        })?;
+
+    let response_body = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nYou may now close this window.";
+    let _ = stream.write_all(response_body).await;
+
+    let query = parse_redirect_query(&String::from_utf8_lossy(&buffer[..bytes_read]))?;
+
+    let returned_state = query
+        .get("state")
+        .ok_or_else(|| OpenFireError::AuthenticationFailed {
+            message: "SSO redirect is missing the state parameter".to_string(),
+        })?;
+
+    if returned_state != &request.state {
+        return Err(OpenFireError::AuthenticationFailed {
+            message: "SSO redirect state does not match the request (possible CSRF)".to_string(),
+        });
+    }
+
+    query
+        .get("code")
+        .cloned()
+        .ok_or_else(|| OpenFireError::AuthenticationFailed {
+            message: "SSO redirect is missing the authorization code".to_string(),
+        })
+}
+
+/// Exchange an authorization code for an access token
+pub async fn exchange_code_for_token(
+    provider: &SsoProviderConfig,
+    request: &SsoAuthorizationRequest,
+    code: &str,
+) -> Result<TokenResponse> {
+    let redirect_uri = redirect_uri(provider.redirect_port);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(&provider.token_endpoint)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", request.code_verifier.as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| OpenFireError::ConnectionError {
+            message: format!("Failed to reach the SSO token endpoint: {}", e),
+        })?;
+
+    response
+        .json::<TokenResponse>()
+        .await
+        .map_err(|e| OpenFireError::SerializationError {
+            message: format!("Failed to parse the SSO token response: {}", e),
+        })
+}
+
+fn redirect_uri(port: u16) -> String {
+    format!("http://127.0.0.1:{}/callback", port)
+}
+
+fn random_url_safe_token() -> String {
+    let bytes: [u8; 24] = rand::random();
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char);
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                if let Ok(byte) = u8::from_str_radix(&value[i + 1..i + 3], 16) {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                }
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_redirect_query(request_line: &str) -> Result<HashMap<String, String>> {
+    let first_line = request_line
+        .lines()
+        .next()
+        .ok_or_else(|| OpenFireError::XmppProtocolError {
+            message: "Empty SSO redirect request".to_string(),
+        })?;
+
+    let mut parts = first_line.split_whitespace();
+    let _method = parts.next();
+    let path = parts.next().ok_or_else(|| OpenFireError::XmppProtocolError {
+        message: "Malformed SSO redirect request line".to_string(),
+    })?;
+
+    let query = path.splitn(2, '?').nth(1).unwrap_or_default();
+    let mut params = HashMap::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let mut kv = pair.splitn(2, '=');
+        if let (Some(key), Some(value)) = (kv.next(), kv.next()) {
+            params.insert(key.to_string(), percent_decode(value));
+        }
+    }
+    Ok(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_authorization_request_includes_pkce_challenge() {
+        let provider = SsoProviderConfig {
+            authorization_endpoint: "https://idp.example.com/authorize".to_string(),
+            token_endpoint: "https://idp.example.com/token".to_string(),
+            client_id: "spark".to_string(),
+            redirect_port: 8091,
+            scope: "openid".to_string(),
+            username_hint: None,
+        };
+
+        let request = build_authorization_request(&provider);
+        assert!(request.authorization_url.starts_with("https://idp.example.com/authorize?"));
+        assert!(request.authorization_url.contains("code_challenge_method=S256"));
+        assert!(request.authorization_url.contains(&format!("state={}", request.state)));
+    }
+
+    #[test]
+    fn test_parse_redirect_query_extracts_code_and_state() {
+        let request_line = "GET /callback?code=abc123&state=xyz HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n";
+        let query = parse_redirect_query(request_line).unwrap();
+
+        assert_eq!(query.get("code"), Some(&"abc123".to_string()));
+        assert_eq!(query.get("state"), Some(&"xyz".to_string()));
+    }
+
+    #[test]
+    fn test_percent_decode_handles_encoded_and_plus_characters() {
+        assert_eq!(percent_decode("hello%20world"), "hello world");
+        assert_eq!(percent_decode("a+b"), "a b");
+    }
+
+    #[tokio::test]
+    async fn test_await_redirect_rejects_state_mismatch() {
+        let provider = SsoProviderConfig {
+            authorization_endpoint: "https://idp.example.com/authorize".to_string(),
+            token_endpoint: "https://idp.example.com/token".to_string(),
+            client_id: "spark".to_string(),
+            redirect_port: 18181,
+            scope: "openid".to_string(),
+            username_hint: None,
+        };
+        let request = build_authorization_request(&provider);
+
+        let browser = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", 18181))
+                .await
+                .unwrap();
+            stream
+                .write_all(b"GET /callback?code=abc123&state=not-the-real-state HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let result = await_redirect(&request, 18181, Duration::from_secs(5)).await;
+        browser.await.unwrap();
+
+        assert!(matches!(result, Err(OpenFireError::AuthenticationFailed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_await_redirect_extracts_code_on_matching_state() {
+        let provider = SsoProviderConfig {
+            authorization_endpoint: "https://idp.example.com/authorize".to_string(),
+            token_endpoint: "https://idp.example.com/token".to_string(),
+            client_id: "spark".to_string(),
+            redirect_port: 18182,
+            scope: "openid".to_string(),
+            username_hint: None,
+        };
+        let request = build_authorization_request(&provider);
+        let state = request.state.clone();
+
+        let browser = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", 18182))
+                .await
+                .unwrap();
+            let line = format!("GET /callback?code=abc123&state={} HTTP/1.1\r\n\r\n", state);
+            stream.write_all(line.as_bytes()).await.unwrap();
+        });
+
+        let result = await_redirect(&request, 18182, Duration::from_secs(5)).await;
+        browser.await.unwrap();
+
+        assert_eq!(result.unwrap(), "abc123");
+    }
+
+    #[tokio::test]
+    async fn test_await_redirect_times_out_without_a_connection() {
+        let provider = SsoProviderConfig {
+            authorization_endpoint: "https://idp.example.com/authorize".to_string(),
+            token_endpoint: "https://idp.example.com/token".to_string(),
+            client_id: "spark".to_string(),
+            redirect_port: 18183,
+            scope: "openid".to_string(),
+            username_hint: None,
+        };
+        let request = build_authorization_request(&provider);
+
+        let result = await_redirect(&request, 18183, Duration::from_millis(100)).await;
+        assert!(matches!(result, Err(OpenFireError::TimeoutError { .. })));
+    }
+}
@@ -0,0 +1,419 @@
+//! Namespace-aware streaming parser that turns raw XMPP bytes off the wire
+//! into typed `XmppEvent`s, mirroring `Message`/`Presence`'s `to_xml()` codec
+
+use crate::communication::{current_timestamp, Message, MessageType, Presence, PresenceStatus, XmppEvent};
+use crate::error::{OpenFireError, Result};
+use crate::mam;
+use quick_xml::events::Event;
+use quick_xml::reader::NsReader;
+use std::collections::HashMap;
+
+/// Pull complete top-level stanzas (`<message>`, `<presence>`, `<iq>`) out of
+/// `buffer`, parsing each into an `XmppEvent` and removing its bytes from the
+/// front of the buffer. Any trailing partial stanza is left for the next read.
+pub fn drain_stanzas(buffer: &mut Vec<u8>) -> Result<Vec<XmppEvent>> {
+    let mut events = Vec::new();
+
+    loop {
+        let text = String::from_utf8_lossy(buffer).into_owned();
+        let Some((stanza_xml, consumed)) = next_complete_stanza(&text) else {
+            break;
+        };
+
+        if let Some(event) = parse_stanza(&stanza_xml)? {
+            events.push(event);
+        }
+
+        buffer.drain(..consumed.min(buffer.len()));
+    }
+
+    Ok(events)
+}
+
+/// Scan `text` for the first complete `<message>`, `<presence>`, or `<iq>`
+/// element (ignoring anything outside one, e.g. a `<stream:stream>` root tag),
+/// returning its XML and the byte offset its end tag finishes at
+pub(crate) fn next_complete_stanza(text: &str) -> Option<(String, usize)> {
+    next_complete_element(text, &is_stanza_root)
+}
+
+/// Scan `text` for the first complete top-level element whose local name
+/// satisfies `is_root` (ignoring anything outside one, e.g. a still-open
+/// `<stream:stream>` root tag), returning its XML and the byte offset its
+/// end tag finishes at. Shared by stanza draining here and by the SASL
+/// handshake's `<stream:features>`/`<challenge>`/`<success>` reads in `auth`.
+pub(crate) fn next_complete_element(text: &str, is_root: &dyn Fn(&str) -> bool) -> Option<(String, usize)> {
+    let mut reader = NsReader::from_str(text);
+    reader.config_mut().trim_text(true);
+
+    let mut depth: u32 = 0;
+    let mut root_name: Option<String> = None;
+    let mut start_offset: usize = 0;
+
+    loop {
+        let before = reader.buffer_position() as usize;
+        match reader.read_event() {
+            Ok(Event::Eof) => return None,
+            Ok(Event::Start(e)) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if depth == 0 {
+                    if !is_root(&local) {
+                        continue;
+                    }
+                    root_name = Some(local);
+                    start_offset = before;
+                    depth = 1;
+                } else if root_name.as_deref() == Some(local.as_str()) {
+                    depth += 1;
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if depth == 0 && is_root(&local) {
+                    let end = reader.buffer_position() as usize;
+                    return Some((text[before..end].to_string(), end));
+                }
+            }
+            Ok(Event::End(e)) => {
+                if depth > 0 {
+                    let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                    if root_name.as_deref() == Some(local.as_str()) {
+                        depth -= 1;
+                        if depth == 0 {
+                            let end = reader.buffer_position() as usize;
+                            return Some((text[start_offset..end].to_string(), end));
+                        }
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(_) => return None,
+        }
+    }
+}
+
+fn is_stanza_root(name: &str) -> bool {
+    matches!(name, "message" | "presence" | "iq")
+}
+
+/// Read a single attribute's unescaped value off a `quick_xml` start/empty
+/// tag by its local (namespace-stripped) name. Shared by every module that
+/// hand-parses XMPP/disco XML with `quick_xml` directly (`upload`, `discovery`).
+pub(crate) fn find_attr(e: &quick_xml::events::BytesStart, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.local_name().as_ref() == name.as_bytes())
+        .map(|a| a.unescape_value().unwrap_or_default().into_owned())
+}
+
+/// Escape the five XML predefined entities in `value`, safe for use inside
+/// either an element's text content or an attribute value. Shared by every
+/// module that hand-builds XMPP stanza XML (`communication`, `upload`, `bookmarks`).
+pub(crate) fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
+
+/// Parse a single complete stanza fragment into its corresponding `XmppEvent`.
+/// Stanza roots this client doesn't yet act on parse to `None`.
+fn parse_stanza(xml: &str) -> Result<Option<XmppEvent>> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut root: Option<String> = None;
+    let mut attrs: HashMap<String, String> = HashMap::new();
+    let mut current_child: Option<String> = None;
+    let mut subject = None;
+    let mut body = String::new();
+    let mut thread = None;
+    let mut show: Option<String> = None;
+    let mut status_message = None;
+    let mut priority: i8 = 0;
+    let mut is_mam_result = false;
+    // Depth inside a MAM `<result>` element (0 when outside one). A
+    // queryid-less result falls through to the generic handling below, but
+    // its `<forwarded><message><body>` is itself a nested message -- this
+    // file's flat, depth-less `current_child` tracking would otherwise let
+    // that inner body/subject/thread bleed into the outer stanza's fields.
+    let mut mam_result_depth: u32 = 0;
+
+    loop {
+        let event = reader.read_event().map_err(|e| OpenFireError::XmppProtocolError {
+            message: format!("Failed to parse stanza: {}", e),
+        })?;
+
+        match event {
+            Event::Eof => break,
+            Event::Start(e) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if root.is_none() {
+                    root = Some(local);
+                    for attr in e.attributes().flatten() {
+                        let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned();
+                        let value = attr.unescape_value().unwrap_or_default().into_owned();
+                        attrs.insert(key, value);
+                    }
+                } else {
+                    let is_result = local == "result" && find_attr(&e, "xmlns").as_deref() == Some(mam::MAM_NS);
+                    if is_result {
+                        is_mam_result = true;
+                    }
+                    if is_result || mam_result_depth > 0 {
+                        mam_result_depth += 1;
+                    }
+                    current_child = Some(local);
+                }
+            }
+            Event::Empty(e) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if root.is_none() {
+                    root = Some(local);
+                    for attr in e.attributes().flatten() {
+                        let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned();
+                        let value = attr.unescape_value().unwrap_or_default().into_owned();
+                        attrs.insert(key, value);
+                    }
+                } else {
+                    if local == "result" && find_attr(&e, "xmlns").as_deref() == Some(mam::MAM_NS) {
+                        is_mam_result = true;
+                    }
+                    current_child = Some(local);
+                }
+            }
+            Event::Text(t) => {
+                if mam_result_depth == 0 {
+                    let text = t.unescape().unwrap_or_default().into_owned();
+                    match current_child.as_deref() {
+                        Some("body") => body.push_str(&text),
+                        Some("subject") => subject = Some(text),
+                        Some("thread") => thread = Some(text),
+                        Some("show") => show = Some(text),
+                        Some("status") => status_message = Some(text),
+                        Some("priority") => priority = text.trim().parse().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(_) => {
+                current_child = None;
+                if mam_result_depth > 0 {
+                    mam_result_depth -= 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if is_mam_result && root.as_deref() == Some("message") {
+        // A `queryid`-less `<result/>` (malformed, or some element that
+        // merely reuses the MAM namespace) isn't a result we can correlate
+        // to a query -- fall through to generic handling below rather than
+        // silently dropping the message.
+        if let Some((query_id, message)) = mam::parse_result_message(xml)? {
+            return Ok(Some(XmppEvent::MamResult { query_id, message }));
+        }
+    }
+
+    match root.as_deref() {
+        Some("message") => {
+            let message_type = match attrs.get("type").map(String::as_str) {
+                Some("groupchat") => MessageType::GroupChat,
+                Some("headline") => MessageType::Headline,
+                Some("error") => MessageType::Error,
+                Some("normal") => MessageType::Normal,
+                _ => MessageType::Chat,
+            };
+
+            Ok(Some(XmppEvent::MessageReceived(Message {
+                id: attrs.remove("id").unwrap_or_default(),
+                from: attrs.remove("from").unwrap_or_default(),
+                to: attrs.remove("to").unwrap_or_default(),
+                message_type,
+                subject,
+                body,
+                timestamp: current_timestamp(),
+                thread,
+            })))
+        }
+        Some("presence") => {
+            let from = attrs.remove("from").unwrap_or_default();
+
+            match attrs.get("type").map(String::as_str) {
+                Some("subscribe") => return Ok(Some(XmppEvent::SubscriptionRequest(from))),
+                Some(kind @ ("subscribed" | "unsubscribe" | "unsubscribed")) => {
+                    return Ok(Some(XmppEvent::SubscriptionPresence {
+                        jid: from,
+                        kind: kind.to_string(),
+                    }));
+                }
+                _ => {}
+            }
+
+            let status = if attrs.get("type").map(String::as_str) == Some("unavailable") {
+                PresenceStatus::Unavailable
+            } else {
+                match show.as_deref() {
+                    Some("away") => PresenceStatus::Away,
+                    Some("dnd") => PresenceStatus::DoNotDisturb,
+                    Some("xa") => PresenceStatus::ExtendedAway,
+                    _ => PresenceStatus::Available,
+                }
+            };
+
+            Ok(Some(XmppEvent::PresenceUpdated(Presence {
+                jid: from,
+                status,
+                status_message,
+                priority,
+                timestamp: current_timestamp(),
+            })))
+        }
+        Some("iq") => Ok(Some(XmppEvent::IqReceived {
+            id: attrs.remove("id").unwrap_or_default(),
+            xml: xml.to_string(),
+        })),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drain_stanzas_parses_a_single_message() {
+        let mut buffer = b"<message type='chat' id='1' from='a@b' to='c@d'><body>hi</body></message>".to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            XmppEvent::MessageReceived(message) => {
+                assert_eq!(message.body, "hi");
+                assert_eq!(message.from, "a@b");
+                assert_eq!(message.message_type, MessageType::Chat);
+            }
+            other => panic!("expected MessageReceived, got {:?}", other),
+        }
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_drain_stanzas_parses_back_to_back_stanzas() {
+        let mut buffer = b"<presence from='a@b'><show>away</show></presence><message type='chat' id='1' from='a@b' to='c@d'><body>hi</body></message>".to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], XmppEvent::PresenceUpdated(_)));
+        assert!(matches!(events[1], XmppEvent::MessageReceived(_)));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_drain_stanzas_leaves_partial_stanza_buffered() {
+        let mut buffer = b"<message type='chat' id='1' from='a@b' to='c@d'><body>hi".to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert!(events.is_empty());
+        assert!(!buffer.is_empty());
+    }
+
+    #[test]
+    fn test_drain_stanzas_skips_stream_root_and_parses_nested_stanza() {
+        let mut buffer =
+            b"<stream:stream xmlns:stream='http://etherx.jabber.org/streams'><presence from='a@b'/></stream:stream>"
+                .to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], XmppEvent::PresenceUpdated(_)));
+    }
+
+    #[test]
+    fn test_drain_stanzas_parses_iq_result_with_its_id() {
+        let mut buffer =
+            b"<iq type='result' id='slot1' from='upload.localhost' to='a@b'><slot/></iq>".to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            XmppEvent::IqReceived { id, xml } => {
+                assert_eq!(id, "slot1");
+                assert!(xml.contains("<slot/>"));
+            }
+            other => panic!("expected IqReceived, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_drain_stanzas_parses_mam_result_as_its_own_event() {
+        let mut buffer = b"<message from='juliet@example.com' to='romeo@example.com/laptop'>\
+            <result xmlns='urn:xmpp:mam:2' queryid='q1' id='28482-98726-73623'>\
+            <forwarded xmlns='urn:xmpp:forward:0'>\
+            <delay xmlns='urn:xmpp:delay' stamp='2010-07-10T23:08:25Z'/>\
+            <message xmlns='jabber:client' from='witch@shakespeare.lit' to='macbeth@shakespeare.lit' type='chat'>\
+            <body>Hail to thee</body></message>\
+            </forwarded></result></message>"
+            .to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            XmppEvent::MamResult { query_id, message } => {
+                assert_eq!(query_id, "q1");
+                assert_eq!(message.from, "witch@shakespeare.lit");
+                assert_eq!(message.body, "Hail to thee");
+            }
+            other => panic!("expected MamResult, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_drain_stanzas_falls_back_to_generic_message_without_a_queryid() {
+        let mut buffer = b"<message from='a@b' to='c@d'>\
+            <result xmlns='urn:xmpp:mam:2' id='1'><forwarded xmlns='urn:xmpp:forward:0'>\
+            <message><body>stray result</body></message></forwarded></result>\
+            </message>"
+            .to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            XmppEvent::MessageReceived(message) => {
+                assert_eq!(message.from, "a@b");
+                assert_eq!(message.body, "");
+            }
+            other => panic!("expected a MessageReceived fallback, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_drain_stanzas_parses_subscribe_presence_as_a_request() {
+        let mut buffer = b"<presence from='alice@localhost' type='subscribe'/>".to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            XmppEvent::SubscriptionRequest(jid) => assert_eq!(jid, "alice@localhost"),
+            other => panic!("expected SubscriptionRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_drain_stanzas_parses_subscribed_presence() {
+        let mut buffer = b"<presence from='alice@localhost' type='subscribed'/>".to_vec();
+        let events = drain_stanzas(&mut buffer).unwrap();
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            XmppEvent::SubscriptionPresence { jid, kind } => {
+                assert_eq!(jid, "alice@localhost");
+                assert_eq!(kind, "subscribed");
+            }
+            other => panic!("expected SubscriptionPresence, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Time-limited HMAC token authentication, an alternative to password-based
+//! SASL for password-reset / magic-link style flows where an out-of-band
+//! component mints the token and this crate only verifies it.
+
+use crate::error::{OpenFireError, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Validates and issues tokens of the form
+/// `base64(username|expiry_unix)|HMAC-SHA256(secret, username|expiry_unix)`
+pub struct TokenAuthenticator {
+    secret: Vec<u8>,
+}
+
+impl TokenAuthenticator {
+    /// Create an authenticator backed by a server-shared secret
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: secret.into(),
+        }
+    }
+
+    /// Mint a token for `username` valid for `ttl`, for testing or for an
+    /// out-of-band component (e.g. a password-reset service) to hand out
+    pub fn issue(&self, username: &str, ttl: Duration) -> String {
+        let expiry = current_timestamp() + ttl.as_secs();
+        let payload = format!("{}|{}", username, expiry);
+        let mac = self.hmac(payload.as_bytes());
+
+        format!("{}|{}", STANDARD.encode(payload.as_bytes()), STANDARD.encode(mac))
+    }
+
+    /// Validate `token` for `username`, rejecting expired tokens, signature
+    /// mismatches, and tokens minted for a different username
+    pub fn validate(&self, username: &str, token: &str) -> Result<()> {
+        let mut parts = token.splitn(2, '|');
+        let payload_b64 = parts.next().unwrap_or_default();
+        let mac_b64 = parts
+            .next()
+            .ok_or_else(|| OpenFireError::InvalidCredentials {
+                message: "Token is missing its signature".to_string(),
+            })?;
+
+        let payload = STANDARD
+            .decode(payload_b64)
+            .map_err(|e| OpenFireError::InvalidCredentials {
+                message: format!("Invalid base64 token payload: {}", e),
+            })?;
+        let provided_mac = STANDARD
+            .decode(mac_b64)
+            .map_err(|e| OpenFireError::InvalidCredentials {
+                message: format!("Invalid base64 token signature: {}", e),
+            })?;
+
+        let expected_mac = self.hmac(&payload);
+        if !constant_time_eq(&expected_mac, &provided_mac) {
+            return Err(OpenFireError::AuthenticationFailed {
+                message: "Token signature verification failed".to_string(),
+            });
+        }
+
+        let payload_str = String::from_utf8(payload).map_err(|e| OpenFireError::InvalidCredentials {
+            message: format!("Token payload is not valid UTF-8: {}", e),
+        })?;
+        let mut fields = payload_str.splitn(2, '|');
+        let token_username = fields.next().unwrap_or_default();
+        let expiry: u64 = fields
+            .next()
+            .ok_or_else(|| OpenFireError::InvalidCredentials {
+                message: "Token payload is missing an expiry".to_string(),
+            })?
+            .parse()
+            .map_err(|e| OpenFireError::InvalidCredentials {
+                message: format!("Invalid expiry in token payload: {}", e),
+            })?;
+
+        if token_username != username {
+            return Err(OpenFireError::AuthenticationFailed {
+                message: "Token was not issued for this username".to_string(),
+            });
+        }
+
+        if current_timestamp() > expiry {
+            return Err(OpenFireError::AuthenticationFailed {
+                message: "Token has expired".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn hmac(&self, data: &[u8]) -> Vec<u8> {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issued_token_validates() {
+        let authenticator = TokenAuthenticator::new("shared-secret".as_bytes());
+        let token = authenticator.issue("alice", Duration::from_secs(60));
+
+        assert!(authenticator.validate("alice", &token).is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_wrong_username() {
+        let authenticator = TokenAuthenticator::new("shared-secret".as_bytes());
+        let token = authenticator.issue("alice", Duration::from_secs(60));
+
+        assert!(authenticator.validate("bob", &token).is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_expired_token() {
+        let authenticator = TokenAuthenticator::new("shared-secret".as_bytes());
+        let token = authenticator.issue("alice", Duration::from_secs(0));
+
+        // Expiry equals "now"; a moment later it must be considered expired.
+        std::thread::sleep(Duration::from_secs(1));
+        assert!(authenticator.validate("alice", &token).is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_tampered_signature() {
+        let authenticator = TokenAuthenticator::new("shared-secret".as_bytes());
+        let token = authenticator.issue("alice", Duration::from_secs(60));
+        let mut tampered = token.clone();
+        tampered.push('x');
+
+        assert!(authenticator.validate("alice", &tampered).is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_wrong_secret() {
+        let authenticator = TokenAuthenticator::new("shared-secret".as_bytes());
+        let token = authenticator.issue("alice", Duration::from_secs(60));
+
+        let other = TokenAuthenticator::new("different-secret".as_bytes());
+        assert!(other.validate("alice", &token).is_err());
+    }
+}
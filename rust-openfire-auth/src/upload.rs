@@ -0,0 +1,166 @@
+//! HTTP File Upload (XEP-0363) IQ payloads and response parsing. Driving the
+//! IQ round-trip and the PUT itself is `communication::OpenFireClient`'s job.
+
+use crate::error::{OpenFireError, Result};
+use crate::stanza::{escape_xml, find_attr};
+use quick_xml::events::Event;
+use quick_xml::reader::NsReader;
+use std::collections::HashMap;
+
+/// An upload slot granted by the server's HTTP-upload component: PUT file
+/// bytes to `put_url` (carrying `put_headers`), then share `get_url`
+#[derive(Debug, Clone)]
+pub struct UploadSlot {
+    pub put_url: String,
+    pub get_url: String,
+    pub put_headers: HashMap<String, String>,
+}
+
+/// Build the `<request/>` IQ payload for a `urn:xmpp:http:upload:0` slot
+pub(crate) fn slot_request_payload(filename: &str, size: u64, content_type: &str) -> String {
+    format!(
+        "<request xmlns='urn:xmpp:http:upload:0' filename='{}' size='{}' content-type='{}'/>",
+        escape_xml(filename),
+        size,
+        escape_xml(content_type)
+    )
+}
+
+/// Parse a `<slot xmlns='urn:xmpp:http:upload:0'>` IQ result into its PUT/GET
+/// URLs and any extra headers the PUT request must carry
+pub(crate) fn parse_upload_slot(xml: &str) -> Result<UploadSlot> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut put_url = None;
+    let mut get_url = None;
+    let mut put_headers = HashMap::new();
+    let mut current: Option<String> = None;
+    let mut current_header_name: Option<String> = None;
+
+    loop {
+        match reader.read_event().map_err(|e| OpenFireError::XmppProtocolError {
+            message: format!("Failed to parse upload slot response: {}", e),
+        })? {
+            Event::Start(e) | Event::Empty(e) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match local.as_str() {
+                    "header" => {
+                        current_header_name = find_attr(&e, "name");
+                    }
+                    "put" => put_url = find_attr(&e, "url"),
+                    "get" => get_url = find_attr(&e, "url"),
+                    _ => {}
+                }
+                current = Some(local);
+            }
+            Event::Text(t) => {
+                if current.as_deref() == Some("header") {
+                    if let Some(name) = current_header_name.take() {
+                        put_headers.insert(name, t.unescape().unwrap_or_default().into_owned());
+                    }
+                }
+            }
+            Event::End(_) => current = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    match (put_url, get_url) {
+        (Some(put_url), Some(get_url)) => Ok(UploadSlot {
+            put_url,
+            get_url,
+            put_headers,
+        }),
+        _ => Err(OpenFireError::XmppProtocolError {
+            message: "Upload slot response missing put or get URL".to_string(),
+        }),
+    }
+}
+
+/// If a disco#info result advertises the `urn:xmpp:http:upload:0` feature,
+/// return the max upload size in bytes if advertised via the XEP-0363 data form
+pub(crate) fn parse_upload_service_info(xml: &str) -> Option<Option<u64>> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut supports_upload = false;
+    let mut in_max_size_field = false;
+    let mut expect_value = false;
+    let mut max_size: Option<u64> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match local.as_str() {
+                    "feature" => {
+                        if find_attr(&e, "var").as_deref() == Some("urn:xmpp:http:upload:0") {
+                            supports_upload = true;
+                        }
+                    }
+                    "field" => {
+                        in_max_size_field = find_attr(&e, "var").as_deref() == Some("max-file-size");
+                    }
+                    "value" if in_max_size_field => expect_value = true,
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(t)) if expect_value => {
+                max_size = t.unescape().ok().and_then(|v| v.parse().ok());
+                expect_value = false;
+            }
+            Ok(Event::End(e)) if e.local_name().as_ref() == b"field" => in_max_size_field = false,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    supports_upload.then_some(max_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_upload_slot_extracts_urls_and_headers() {
+        let xml = "<slot xmlns='urn:xmpp:http:upload:0'>\
+            <put url='https://upload.example.com/abc/file.png'>\
+            <header name='Authorization'>Bearer abc123</header>\
+            </put>\
+            <get url='https://upload.example.com/abc/file.png'/>\
+            </slot>";
+
+        let slot = parse_upload_slot(xml).unwrap();
+        assert_eq!(slot.put_url, "https://upload.example.com/abc/file.png");
+        assert_eq!(slot.get_url, "https://upload.example.com/abc/file.png");
+        assert_eq!(
+            slot.put_headers.get("Authorization"),
+            Some(&"Bearer abc123".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_upload_slot_rejects_missing_urls() {
+        let xml = "<slot xmlns='urn:xmpp:http:upload:0'/>";
+        assert!(parse_upload_slot(xml).is_err());
+    }
+
+    #[test]
+    fn test_parse_upload_service_info_detects_feature_and_max_size() {
+        let xml = "<query xmlns='http://jabber.org/protocol/disco#info'>\
+            <feature var='urn:xmpp:http:upload:0'/>\
+            <x xmlns='jabber:x:data' type='result'>\
+            <field var='max-file-size'><value>10485760</value></field>\
+            </x></query>";
+        assert_eq!(parse_upload_service_info(xml), Some(Some(10485760)));
+    }
+
+    #[test]
+    fn test_parse_upload_service_info_returns_none_without_feature() {
+        let xml = "<query xmlns='http://jabber.org/protocol/disco#info'><feature var='jabber:iq:version'/></query>";
+        assert_eq!(parse_upload_service_info(xml), None);
+    }
+}